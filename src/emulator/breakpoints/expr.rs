@@ -0,0 +1,683 @@
+//! Small expression language used by breakpoint conditions and log messages.
+//!
+//! Operands are numbers (`42`, `0x2A`, `2Ah`, `$2A`, `0b101010`), registers
+//! (`A`, `HL`, `IXH`, `AF'`, `PSW`, ...), flags (`ZF`, `CF`, `SF`, `HF`,
+//! `PF`/`VF`, `NF`) and memory reads. `(HL)`, `(IX+4)` and `(0x4000)` read a
+//! byte like in Z80 assembly, any other parenthesized expression is a plain
+//! group; `[expr]` reads the byte at an arbitrary address. Operators follow C
+//! precedence. Registers the selected cpu does not have fail at evaluation.
+
+use crate::emulator::cpu::RegisterState;
+use emu_lib::memory::MemoryDevice;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reg {
+    A,
+    F,
+    B,
+    C,
+    D,
+    E,
+    H,
+    L,
+    AF,
+    PSW,
+    BC,
+    DE,
+    HL,
+    AFAlt,
+    BCAlt,
+    DEAlt,
+    HLAlt,
+    SP,
+    PC,
+    IX,
+    IY,
+    IXH,
+    IXL,
+    IYH,
+    IYL,
+    I,
+    R,
+}
+
+impl Reg {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_uppercase().as_str() {
+            "A" => Reg::A,
+            "F" => Reg::F,
+            "B" => Reg::B,
+            "C" => Reg::C,
+            "D" => Reg::D,
+            "E" => Reg::E,
+            "H" => Reg::H,
+            "L" => Reg::L,
+            "AF" => Reg::AF,
+            "PSW" => Reg::PSW,
+            "BC" => Reg::BC,
+            "DE" => Reg::DE,
+            "HL" => Reg::HL,
+            "AF'" => Reg::AFAlt,
+            "BC'" => Reg::BCAlt,
+            "DE'" => Reg::DEAlt,
+            "HL'" => Reg::HLAlt,
+            "SP" => Reg::SP,
+            "PC" => Reg::PC,
+            "IX" => Reg::IX,
+            "IY" => Reg::IY,
+            "IXH" => Reg::IXH,
+            "IXL" => Reg::IXL,
+            "IYH" => Reg::IYH,
+            "IYL" => Reg::IYL,
+            "I" => Reg::I,
+            "R" => Reg::R,
+            _ => return None,
+        })
+    }
+
+    fn is_pair(&self) -> bool {
+        matches!(
+            self,
+            Reg::AF
+                | Reg::PSW
+                | Reg::BC
+                | Reg::DE
+                | Reg::HL
+                | Reg::SP
+                | Reg::PC
+                | Reg::IX
+                | Reg::IY
+        )
+    }
+
+    /// Register the value lives in, and which byte of it.
+    fn source(&self) -> (&'static str, Part) {
+        match self {
+            Reg::A => ("AF", Part::High),
+            Reg::F => ("AF", Part::Low),
+            Reg::B => ("BC", Part::High),
+            Reg::C => ("BC", Part::Low),
+            Reg::D => ("DE", Part::High),
+            Reg::E => ("DE", Part::Low),
+            Reg::H => ("HL", Part::High),
+            Reg::L => ("HL", Part::Low),
+            Reg::AF => ("AF", Part::Word),
+            Reg::PSW => ("PSW", Part::Word),
+            Reg::BC => ("BC", Part::Word),
+            Reg::DE => ("DE", Part::Word),
+            Reg::HL => ("HL", Part::Word),
+            Reg::AFAlt => ("AF'", Part::Word),
+            Reg::BCAlt => ("BC'", Part::Word),
+            Reg::DEAlt => ("DE'", Part::Word),
+            Reg::HLAlt => ("HL'", Part::Word),
+            Reg::SP => ("SP", Part::Word),
+            Reg::PC => ("PC", Part::Word),
+            Reg::IX => ("IX", Part::Word),
+            Reg::IY => ("IY", Part::Word),
+            Reg::IXH => ("IX", Part::High),
+            Reg::IXL => ("IX", Part::Low),
+            Reg::IYH => ("IY", Part::High),
+            Reg::IYL => ("IY", Part::Low),
+            Reg::I => ("I", Part::Word),
+            Reg::R => ("R", Part::Word),
+        }
+    }
+
+    fn read<R: RegisterState>(&self, regs: &R) -> Result<i64, String> {
+        let (name, part) = self.source();
+        let value = regs
+            .get(name)
+            .ok_or_else(|| format!("{} has no {} register", R::CPU, name))?;
+        Ok(match part {
+            Part::Word => value as i64,
+            Part::High => (value >> 8) as i64,
+            Part::Low => (value & 0xFF) as i64,
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Part {
+    Word,
+    High,
+    Low,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flag {
+    S,
+    Z,
+    H,
+    PV,
+    N,
+    C,
+}
+
+impl Flag {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name.to_ascii_uppercase().as_str() {
+            "SF" => Flag::S,
+            "ZF" => Flag::Z,
+            "HF" => Flag::H,
+            "PF" | "VF" => Flag::PV,
+            "NF" => Flag::N,
+            "CF" => Flag::C,
+            _ => return None,
+        })
+    }
+
+    fn bit(&self) -> u8 {
+        match self {
+            Flag::S => 7,
+            Flag::Z => 6,
+            Flag::H => 4,
+            Flag::PV => 2,
+            Flag::N => 1,
+            Flag::C => 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnaryOp {
+    Not,
+    Neg,
+    BitNot,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOp {
+    fn parse(op: &str) -> Option<(Self, u8)> {
+        Some(match op {
+            "||" => (BinaryOp::Or, 1),
+            "&&" => (BinaryOp::And, 2),
+            "|" => (BinaryOp::BitOr, 3),
+            "^" => (BinaryOp::BitXor, 4),
+            "&" => (BinaryOp::BitAnd, 5),
+            "==" => (BinaryOp::Eq, 6),
+            "!=" => (BinaryOp::Ne, 6),
+            "<" => (BinaryOp::Lt, 7),
+            "<=" => (BinaryOp::Le, 7),
+            ">" => (BinaryOp::Gt, 7),
+            ">=" => (BinaryOp::Ge, 7),
+            "<<" => (BinaryOp::Shl, 8),
+            ">>" => (BinaryOp::Shr, 8),
+            "+" => (BinaryOp::Add, 9),
+            "-" => (BinaryOp::Sub, 9),
+            "*" => (BinaryOp::Mul, 10),
+            "/" => (BinaryOp::Div, 10),
+            "%" => (BinaryOp::Rem, 10),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Number(i64),
+    Register(Reg),
+    Flag(Flag),
+    Deref(Box<Expr>),
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Ident(String),
+    Op(&'static str),
+    Open(char),
+    Close(char),
+}
+
+const OPERATORS: [&str; 20] = [
+    "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "|", "^", "&", "<", ">", "+", "-", "*", "/",
+    "%", "!", "~",
+];
+
+fn parse_number(text: &str) -> Option<i64> {
+    let lower = text.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x").or_else(|| lower.strip_prefix('$')) {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()
+    } else if let Some(hex) = lower.strip_suffix('h') {
+        i64::from_str_radix(hex, 16).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let c = chars[pos];
+        if c.is_whitespace() {
+            pos += 1;
+        } else if c.is_ascii_alphanumeric() || c == '_' || c == '$' {
+            let start = pos;
+            pos += 1;
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '_') {
+                pos += 1;
+            }
+            if pos < chars.len() && chars[pos] == '\'' {
+                pos += 1;
+            }
+            let word: String = chars[start..pos].iter().collect();
+            let is_register = Reg::parse(&word).is_some() || Flag::parse(&word).is_some();
+            match parse_number(&word) {
+                Some(number) if !is_register => tokens.push(Token::Number(number)),
+                _ if c.is_ascii_digit() || c == '$' => {
+                    return Err(format!("invalid number \"{}\"", word))
+                }
+                _ => tokens.push(Token::Ident(word)),
+            }
+        } else if matches!(c, '(' | '[') {
+            tokens.push(Token::Open(c));
+            pos += 1;
+        } else if matches!(c, ')' | ']') {
+            tokens.push(Token::Close(c));
+            pos += 1;
+        } else {
+            let rest: String = chars[pos..].iter().take(2).collect();
+            let op = OPERATORS
+                .iter()
+                .find(|op| rest.starts_with(**op))
+                .ok_or_else(|| format!("unexpected character '{}'", c))?;
+            tokens.push(Token::Op(op));
+            pos += op.len();
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect_close(&mut self, close: char) -> Result<(), String> {
+        match self.next() {
+            Some(Token::Close(c)) if c == close => Ok(()),
+            _ => Err(format!("expected '{}'", close)),
+        }
+    }
+
+    fn binary(&mut self, min_precedence: u8) -> Result<Expr, String> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Op(op)) = self.peek() {
+            let Some((op, precedence)) = BinaryOp::parse(op) else {
+                break;
+            };
+            if precedence < min_precedence {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.binary(precedence + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let op = match self.peek() {
+            Some(Token::Op("!")) => UnaryOp::Not,
+            Some(Token::Op("-")) => UnaryOp::Neg,
+            Some(Token::Op("~")) => UnaryOp::BitNot,
+            _ => return self.primary(),
+        };
+        self.pos += 1;
+        Ok(Expr::Unary(op, Box::new(self.unary()?)))
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Token::Number(number)) => Ok(Expr::Number(number)),
+            Some(Token::Ident(name)) => Reg::parse(&name)
+                .map(Expr::Register)
+                .or_else(|| Flag::parse(&name).map(Expr::Flag))
+                .ok_or_else(|| format!("unknown register or flag \"{}\"", name)),
+            Some(Token::Open('[')) => {
+                let inner = self.binary(0)?;
+                self.expect_close(']')?;
+                Ok(Expr::Deref(Box::new(inner)))
+            }
+            Some(Token::Open(_)) => {
+                let inner = self.binary(0)?;
+                self.expect_close(')')?;
+                let is_address = match &inner {
+                    Expr::Number(_) => true,
+                    Expr::Register(reg) => reg.is_pair(),
+                    Expr::Binary(BinaryOp::Add | BinaryOp::Sub, base, _) => {
+                        matches!(**base, Expr::Register(Reg::IX | Reg::IY))
+                    }
+                    _ => false,
+                };
+                Ok(match is_address {
+                    true => Expr::Deref(Box::new(inner)),
+                    false => inner,
+                })
+            }
+            Some(token) => Err(format!("unexpected {:?}", token)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Expr, String> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let expr = parser.binary(0)?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(token) => Err(format!("unexpected {:?}", token)),
+        }
+    }
+
+    pub fn eval(
+        &self,
+        regs: &impl RegisterState,
+        memory: &impl MemoryDevice,
+    ) -> Result<i64, String> {
+        Ok(match self {
+            Expr::Number(number) => *number,
+            Expr::Register(reg) => reg.read(regs)?,
+            Expr::Flag(flag) => (Reg::F.read(regs)? >> flag.bit()) & 1,
+            Expr::Deref(address) => {
+                let address = address.eval(regs, memory)? as u16;
+                memory
+                    .read_8(address)
+                    .map_err(|err| format!("cannot read 0x{:04X}: {}", address, err))?
+                    as i64
+            }
+            Expr::Unary(op, operand) => {
+                let value = operand.eval(regs, memory)?;
+                match op {
+                    UnaryOp::Not => (value == 0) as i64,
+                    UnaryOp::Neg => value.wrapping_neg(),
+                    UnaryOp::BitNot => !value,
+                }
+            }
+            Expr::Binary(BinaryOp::Or, lhs, rhs) => {
+                (lhs.eval(regs, memory)? != 0 || rhs.eval(regs, memory)? != 0) as i64
+            }
+            Expr::Binary(BinaryOp::And, lhs, rhs) => {
+                (lhs.eval(regs, memory)? != 0 && rhs.eval(regs, memory)? != 0) as i64
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = lhs.eval(regs, memory)?;
+                let rhs = rhs.eval(regs, memory)?;
+                match op {
+                    BinaryOp::BitOr => lhs | rhs,
+                    BinaryOp::BitXor => lhs ^ rhs,
+                    BinaryOp::BitAnd => lhs & rhs,
+                    BinaryOp::Eq => (lhs == rhs) as i64,
+                    BinaryOp::Ne => (lhs != rhs) as i64,
+                    BinaryOp::Lt => (lhs < rhs) as i64,
+                    BinaryOp::Le => (lhs <= rhs) as i64,
+                    BinaryOp::Gt => (lhs > rhs) as i64,
+                    BinaryOp::Ge => (lhs >= rhs) as i64,
+                    BinaryOp::Shl => lhs.wrapping_shl(rhs as u32),
+                    BinaryOp::Shr => lhs.wrapping_shr(rhs as u32),
+                    BinaryOp::Add => lhs.wrapping_add(rhs),
+                    BinaryOp::Sub => lhs.wrapping_sub(rhs),
+                    BinaryOp::Mul => lhs.wrapping_mul(rhs),
+                    BinaryOp::Div | BinaryOp::Rem if rhs == 0 => {
+                        return Err("division by zero".to_string())
+                    }
+                    BinaryOp::Div => lhs.wrapping_div(rhs),
+                    BinaryOp::Rem => lhs.wrapping_rem(rhs),
+                    BinaryOp::Or | BinaryOp::And => unreachable!(),
+                }
+            }
+        })
+    }
+}
+
+/// Log message with `{expr}` placeholders, each replaced by the value in hex.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    parts: Vec<TemplatePart>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum TemplatePart {
+    Text(String),
+    Expr(Expr),
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut rest = source;
+        while let Some(open) = rest.find('{') {
+            let close = rest[open..]
+                .find('}')
+                .ok_or("unclosed '{' in log message")?;
+            parts.push(TemplatePart::Text(rest[..open].to_string()));
+            parts.push(TemplatePart::Expr(Expr::parse(
+                &rest[open + 1..open + close],
+            )?));
+            rest = &rest[open + close + 1..];
+        }
+        parts.push(TemplatePart::Text(rest.to_string()));
+        Ok(Self { parts })
+    }
+
+    pub fn render(&self, regs: &impl RegisterState, memory: &impl MemoryDevice) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Text(text) => text.clone(),
+                TemplatePart::Expr(expr) => match expr.eval(regs, memory) {
+                    Ok(value) => format!("{:X}", value),
+                    Err(err) => format!("<{}>", err),
+                },
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::registers::{i8080, z80};
+
+    struct TestMemory(Vec<u8>);
+
+    impl MemoryDevice for TestMemory {
+        fn size(&self) -> usize {
+            self.0.len()
+        }
+
+        fn read_8(&self, addr: u16) -> Result<u8, &'static str> {
+            self.0
+                .get(addr as usize)
+                .copied()
+                .ok_or("Address out of bounds")
+        }
+
+        fn write_8(&mut self, addr: u16, data: u8) -> Result<(), &'static str> {
+            self.write_8_force(addr, data)
+        }
+
+        fn write_8_force(&mut self, addr: u16, data: u8) -> Result<(), &'static str> {
+            let value = self
+                .0
+                .get_mut(addr as usize)
+                .ok_or("Address out of bounds")?;
+            *value = data;
+            Ok(())
+        }
+    }
+
+    fn memory() -> TestMemory {
+        let mut memory = TestMemory((0..=0xFFFF).map(|addr| addr as u8).collect());
+        memory.0[0x4000] = 0xAA;
+        memory.0[0x4014] = 0x55;
+        memory
+    }
+
+    fn registers() -> z80::RegisterFile {
+        z80::RegisterFile {
+            af: 0x1241,
+            bc: 0x0102,
+            hl: 0x4000,
+            hl_alt: 0xBEEF,
+            ix: 0x4010,
+            ..Default::default()
+        }
+    }
+
+    fn eval(source: &str) -> Result<i64, String> {
+        Expr::parse(source)?.eval(&registers(), &memory())
+    }
+
+    #[test]
+    fn numbers() {
+        for source in ["42", "0x2A", "2Ah", "$2A", "0b101010", "0X2a"] {
+            assert_eq!(eval(source), Ok(42), "{}", source);
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3"), Ok(7));
+        assert_eq!(eval("(1 + 2) * 3"), Ok(9));
+        assert_eq!(eval("10 - 4 - 3"), Ok(3));
+        assert_eq!(eval("1 << 2 + 1"), Ok(8));
+        assert_eq!(eval("1 | 2 & 0"), Ok(1));
+        assert_eq!(eval("6 & 3 == 3"), Ok(0));
+        assert_eq!(eval("1 + 2 == 3 && 2 < 1 || 4 > 3"), Ok(1));
+        assert_eq!(eval("-2 * 3"), Ok(-6));
+        assert_eq!(eval("!0 + ~0"), Ok(0));
+    }
+
+    #[test]
+    fn registers_and_flags() {
+        assert_eq!(eval("A"), Ok(0x12));
+        assert_eq!(eval("F"), Ok(0x41));
+        assert_eq!(eval("BC"), Ok(0x0102));
+        assert_eq!(eval("b == 1 && c == 2"), Ok(1));
+        assert_eq!(eval("HL'"), Ok(0xBEEF));
+        assert_eq!(eval("IXH"), Ok(0x40));
+        assert_eq!(eval("ZF"), Ok(1));
+        assert_eq!(eval("CF"), Ok(1));
+        assert_eq!(eval("SF"), Ok(0));
+    }
+
+    #[test]
+    fn deref_forms() {
+        let deref = |inner: Expr| Ok(Expr::Deref(Box::new(inner)));
+        assert_eq!(Expr::parse("(HL)"), deref(Expr::Register(Reg::HL)));
+        assert_eq!(Expr::parse("(0x4000)"), deref(Expr::Number(0x4000)));
+        assert_eq!(
+            Expr::parse("(IX+4)"),
+            deref(Expr::Binary(
+                BinaryOp::Add,
+                Box::new(Expr::Register(Reg::IX)),
+                Box::new(Expr::Number(4))
+            ))
+        );
+        assert_eq!(
+            Expr::parse("[A + 1]"),
+            deref(Expr::Binary(
+                BinaryOp::Add,
+                Box::new(Expr::Register(Reg::A)),
+                Box::new(Expr::Number(1))
+            ))
+        );
+        // Single registers and other expressions are plain groups
+        assert_eq!(Expr::parse("(A)"), Ok(Expr::Register(Reg::A)));
+        assert_eq!(
+            Expr::parse("(HL + 1)").map(|expr| matches!(expr, Expr::Binary(..))),
+            Ok(true)
+        );
+
+        assert_eq!(eval("(HL)"), Ok(0xAA));
+        assert_eq!(eval("(IX+4)"), Ok(0x55));
+        assert_eq!(eval("(IX-0x10)"), Ok(0xAA));
+        assert_eq!(eval("[HL + 0x14]"), Ok(0x55));
+        assert_eq!(eval("(0x1234)"), Ok(0x34));
+        assert_eq!(eval("(HL) == 0xAA"), Ok(1));
+    }
+
+    #[test]
+    fn malformed_input() {
+        for source in [
+            "", "1 +", "(HL", "[HL)", "1 2", "()", "0x", "0b2", "12zz", "foo", "1 # 2", "* 2",
+        ] {
+            assert!(
+                Expr::parse(source).is_err(),
+                "{:?} should not parse",
+                source
+            );
+        }
+    }
+
+    #[test]
+    fn evaluation_errors() {
+        assert!(eval("1 / 0").is_err());
+        assert!(eval("A % 0").is_err());
+        let regs = i8080::RegisterFile::default();
+        assert!(Expr::parse("IX").unwrap().eval(&regs, &memory()).is_err());
+        assert_eq!(Expr::parse("PSW").unwrap().eval(&regs, &memory()), Ok(0));
+        let small = TestMemory(vec![0; 0x10]);
+        assert!(Expr::parse("(0x20)")
+            .unwrap()
+            .eval(&registers(), &small)
+            .is_err());
+    }
+
+    #[test]
+    fn templates() {
+        let render = |source: &str| {
+            Template::parse(source).map(|template| template.render(&registers(), &memory()))
+        };
+        assert_eq!(render("A={A} HL={HL}"), Ok("A=12 HL=4000".to_string()));
+        assert_eq!(render("no placeholders"), Ok("no placeholders".to_string()));
+        assert_eq!(render("{(HL)}{1+1}"), Ok("AA2".to_string()));
+        assert!(render("{1 / 0}").unwrap().starts_with('<'));
+        assert!(Template::parse("unclosed {A").is_err());
+        assert!(Template::parse("{1 +}").is_err());
+        assert!(Template::parse("{}").is_err());
+    }
+}
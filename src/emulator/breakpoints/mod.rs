@@ -0,0 +1,401 @@
+use super::cpu::UiCpu;
+use super::{style, EmuSignals};
+use emu_lib::cpu::Cpu;
+use emu_lib::emulator::Emulator;
+use expr::{Expr, Template};
+use leptos::logging::{log, warn};
+use leptos::prelude::*;
+use std::collections::BTreeMap;
+use web_sys::HtmlInputElement;
+
+pub mod expr;
+
+const MAX_LOG_LINES: usize = 200;
+
+/// Breakpoint with extra behaviour. Plain breakpoints stay in
+/// `Emulator::breakpoints` so the emulator stops on them by itself; only
+/// these ones are checked by the runner after every instruction.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct Breakpoint {
+    pub condition: Option<(String, Expr)>,
+    pub ignore_count: u32,
+    pub hits: u32,
+    pub log_message: Option<(String, Template)>,
+}
+
+impl Breakpoint {
+    pub fn is_plain(&self) -> bool {
+        self.condition.is_none() && self.ignore_count == 0 && self.log_message.is_none()
+    }
+}
+
+/// What the runner should do after checking the breakpoints.
+pub enum BreakAction {
+    Continue,
+    Stop(String),
+}
+
+/// Copy of the advanced breakpoints used for the duration of one run,
+/// so the signals are not touched for every executed instruction.
+pub struct BreakpointChecker {
+    breakpoints: BTreeMap<u16, Breakpoint>,
+    log: Vec<String>,
+    stop_reason: Option<String>,
+}
+
+impl BreakpointChecker {
+    pub fn check<C: UiCpu>(&mut self, emu: &Emulator<C>) -> BreakAction {
+        let pc = *emu.cpu.registers().pc;
+        let Some(breakpoint) = self.breakpoints.get_mut(&pc) else {
+            return BreakAction::Continue;
+        };
+        let regs = emu.cpu.capture_registers();
+        if let Some((source, condition)) = &breakpoint.condition {
+            match condition.eval(&regs, &emu.memory) {
+                Ok(0) => return BreakAction::Continue,
+                Ok(_) => {}
+                Err(err) => {
+                    let reason = format!("Breakpoint {:04X}: \"{}\" failed: {}", pc, source, err);
+                    self.stop_reason = Some(reason.clone());
+                    return BreakAction::Stop(reason);
+                }
+            }
+        }
+        breakpoint.hits += 1;
+        if breakpoint.hits <= breakpoint.ignore_count {
+            return BreakAction::Continue;
+        }
+        match &breakpoint.log_message {
+            Some((_, template)) => {
+                let line = format!("{:04X}: {}", pc, template.render(&regs, &emu.memory));
+                log!("{}", line);
+                self.log.push(line);
+                BreakAction::Continue
+            }
+            None => {
+                let reason = match &breakpoint.condition {
+                    Some((source, _)) => format!("Breakpoint {:04X} hit: {}", pc, source),
+                    None => format!("Breakpoint {:04X} hit", pc),
+                };
+                self.stop_reason = Some(reason.clone());
+                BreakAction::Stop(reason)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct BreakpointSignals {
+    pub list: RwSignal<BTreeMap<u16, Breakpoint>>,
+    pub log: RwSignal<Vec<String>>,
+    pub status: RwSignal<String>,
+}
+
+impl Default for BreakpointSignals {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BreakpointSignals {
+    pub fn new() -> Self {
+        Self {
+            list: RwSignal::new(BTreeMap::new()),
+            log: RwSignal::new(Vec::new()),
+            status: RwSignal::new(String::new()),
+        }
+    }
+
+    pub fn checker(&self) -> BreakpointChecker {
+        BreakpointChecker {
+            breakpoints: self.list.get_untracked(),
+            log: Vec::new(),
+            stop_reason: None,
+        }
+    }
+
+    /// Publishes hit counts, log lines and the stop reason of a finished run.
+    pub fn commit(&self, checker: BreakpointChecker) {
+        if checker.breakpoints.is_empty() {
+            return;
+        }
+        let hits_changed = self.list.with_untracked(|list| {
+            list.iter()
+                .any(|(addr, bk)| checker.breakpoints.get(addr).map(|c| c.hits) != Some(bk.hits))
+        });
+        if hits_changed {
+            self.list.update(|list| {
+                for (addr, breakpoint) in list.iter_mut() {
+                    if let Some(checked) = checker.breakpoints.get(addr) {
+                        breakpoint.hits = checked.hits;
+                    }
+                }
+            });
+        }
+        if !checker.log.is_empty() {
+            self.log.update(|log| {
+                log.extend(checker.log);
+                let excess = log.len().saturating_sub(MAX_LOG_LINES);
+                log.drain(..excess);
+            });
+        }
+        if let Some(reason) = checker.stop_reason {
+            self.status.set(reason);
+        }
+    }
+
+    pub fn contains<C: UiCpu>(&self, emu: &Emulator<C>, address: u16) -> bool {
+        emu.breakpoints.contains(&address) || self.list.with(|list| list.contains_key(&address))
+    }
+
+    pub fn toggle<C: UiCpu>(&self, emu_signals: EmuSignals<C>, address: u16) {
+        let advanced = self.list.with_untracked(|list| list.contains_key(&address));
+        if advanced {
+            self.list.update(|list| {
+                list.remove(&address);
+            });
+            return;
+        }
+        emu_signals.write.update(|emu| {
+            if emu.breakpoints.contains(&address) {
+                emu.breakpoints.retain(|&x| x != address);
+            } else {
+                emu.breakpoints.push(address);
+            }
+        });
+    }
+
+    /// Applies `change` to the breakpoint at `address`, moving it between the
+    /// emulator's plain list and the advanced list as needed.
+    pub fn edit<C: UiCpu>(
+        &self,
+        emu_signals: EmuSignals<C>,
+        address: u16,
+        change: impl FnOnce(&mut Breakpoint),
+    ) {
+        let mut breakpoint = self
+            .list
+            .with_untracked(|list| list.get(&address).cloned())
+            .unwrap_or_default();
+        change(&mut breakpoint);
+        emu_signals.write.update(|emu| {
+            emu.breakpoints.retain(|&x| x != address);
+            if breakpoint.is_plain() {
+                emu.breakpoints.push(address);
+            }
+        });
+        self.list.update(|list| {
+            match breakpoint.is_plain() {
+                true => list.remove(&address),
+                false => list.insert(address, breakpoint),
+            };
+        });
+    }
+
+    pub fn remove<C: UiCpu>(&self, emu_signals: EmuSignals<C>, address: u16) {
+        emu_signals
+            .write
+            .update(|emu| emu.breakpoints.retain(|&x| x != address));
+        self.list.update(|list| {
+            list.remove(&address);
+        });
+    }
+}
+
+#[component]
+fn BreakpointTr<C: UiCpu>(address: u16) -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let breakpoints = expect_context::<BreakpointSignals>();
+    let breakpoint = move || {
+        breakpoints
+            .list
+            .with(|list| list.get(&address).cloned())
+            .unwrap_or_default()
+    };
+    let condition = move || {
+        breakpoint()
+            .condition
+            .map(|(source, _)| source)
+            .unwrap_or_default()
+    };
+    let message = move || {
+        breakpoint()
+            .log_message
+            .map(|(source, _)| source)
+            .unwrap_or_default()
+    };
+    view! {
+        <tr>
+            <td class=style::tableleft>
+                <span>{format!("{:04X}", address)}</span>
+            </td>
+            <td class=style::tablecell>
+                <input
+                    style:width="100%"
+                    placeholder="always"
+                    prop:value=condition
+                    on:change=move |event| {
+                        let source = event_target_value(&event).trim().to_string();
+                        if source.is_empty() {
+                            breakpoints.edit(emu_signals, address, |bk| bk.condition = None);
+                            return;
+                        }
+                        match Expr::parse(&source) {
+                            Ok(expr) => {
+                                breakpoints
+                                    .edit(emu_signals, address, |bk| bk.condition = Some((source, expr)));
+                            }
+                            Err(err) => {
+                                warn!("Invalid condition \"{}\": {}", source, err);
+                                breakpoints.status.set(format!("Invalid condition: {}", err));
+                                event_target::<HtmlInputElement>(&event).set_value(&condition());
+                            }
+                        }
+                    }
+                />
+            </td>
+            <td class=style::tablecell>
+                <input
+                    style:width="5ch"
+                    prop:value=move || breakpoint().ignore_count.to_string()
+                    on:change=move |event| {
+                        match event_target_value(&event).trim().parse::<u32>() {
+                            Ok(count) => {
+                                breakpoints
+                                    .edit(
+                                        emu_signals,
+                                        address,
+                                        |bk| {
+                                            bk.ignore_count = count;
+                                            bk.hits = 0;
+                                        },
+                                    );
+                            }
+                            Err(_) => {
+                                event_target::<HtmlInputElement>(&event)
+                                    .set_value(&breakpoint().ignore_count.to_string());
+                            }
+                        }
+                    }
+                />
+            </td>
+            <td class=style::tableleft>
+                <span>{move || breakpoint().hits}</span>
+            </td>
+            <td class=style::tablecell>
+                <input
+                    style:width="100%"
+                    placeholder="stop"
+                    prop:value=message
+                    on:change=move |event| {
+                        let source = event_target_value(&event);
+                        if source.trim().is_empty() {
+                            breakpoints.edit(emu_signals, address, |bk| bk.log_message = None);
+                            return;
+                        }
+                        match Template::parse(&source) {
+                            Ok(template) => {
+                                breakpoints
+                                    .edit(
+                                        emu_signals,
+                                        address,
+                                        |bk| bk.log_message = Some((source, template)),
+                                    );
+                            }
+                            Err(err) => {
+                                warn!("Invalid log message \"{}\": {}", source, err);
+                                breakpoints.status.set(format!("Invalid log message: {}", err));
+                                event_target::<HtmlInputElement>(&event).set_value(&message());
+                            }
+                        }
+                    }
+                />
+            </td>
+            <td
+                class=style::tablebutton
+                on:click=move |_| breakpoints.remove(emu_signals, address)
+            >
+                <span>"✕"</span>
+            </td>
+        </tr>
+    }
+}
+
+#[component]
+pub fn BreakpointPanel<C: UiCpu>() -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let breakpoints = expect_context::<BreakpointSignals>();
+    let addresses = move || {
+        let mut addresses = emu_signals.read.with(|emu| emu.breakpoints.clone());
+        addresses.extend(
+            breakpoints
+                .list
+                .with(|list| list.keys().copied().collect::<Vec<_>>()),
+        );
+        addresses.sort();
+        addresses.dedup();
+        addresses
+    };
+    view! {
+        <table style:width="100%" class=style::table>
+            <thead>
+                <tr>
+                    <th class=style::tabletop>
+                        <span>"Bk"</span>
+                    </th>
+                    <th class=style::tabletop style:width="50%">
+                        <span>"Condition"</span>
+                    </th>
+                    <th class=style::tabletop>
+                        <span>"Ignore"</span>
+                    </th>
+                    <th class=style::tabletop>
+                        <span>"Hits"</span>
+                    </th>
+                    <th class=style::tabletop style:width="50%">
+                        <span>"Log message"</span>
+                    </th>
+                    <th class=style::tabletop></th>
+                </tr>
+            </thead>
+            <tbody>
+                <For each=addresses key=|address| *address let:address>
+                    <BreakpointTr<C> address />
+                </For>
+                <tr>
+                    <td class=style::tablecell>
+                        <input
+                            style:width="5ch"
+                            maxlength=4
+                            placeholder="Add"
+                            on:change=move |event| {
+                                let element = event_target::<HtmlInputElement>(&event);
+                                match u16::from_str_radix(&element.value(), 16) {
+                                    Ok(address) => {
+                                        breakpoints.edit(emu_signals, address, |_| {});
+                                        element.set_value("");
+                                    }
+                                    Err(_) => log!("Invalid hex value"),
+                                }
+                            }
+                        />
+                    </td>
+                    <td class=style::tableleft colspan=5 style:padding="0.3rem">
+                        <span>{move || breakpoints.status.get()}</span>
+                    </td>
+                </tr>
+                <For
+                    each=move || breakpoints.log.get().into_iter().enumerate().rev().take(10)
+                    key=|(index, line)| (*index, line.clone())
+                    let:entry
+                >
+                    <tr>
+                        <td class=style::tableleft colspan=6>
+                            <span>{entry.1}</span>
+                        </td>
+                    </tr>
+                </For>
+            </tbody>
+        </table>
+    }
+}
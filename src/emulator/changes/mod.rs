@@ -0,0 +1,100 @@
+use super::cpu::RegisterState;
+use super::style;
+use leptos::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// Number of stops a change stays highlighted for, fading a bit on each one.
+const FADE_STOPS: u8 = 3;
+
+/// Registers and memory modified since the previous stops, with how many
+/// stops ago each one changed.
+#[derive(Clone, Copy)]
+pub struct ChangeSignals {
+    pub registers: RwSignal<HashMap<&'static str, u8>>,
+    pub memory: RwSignal<HashMap<u16, u8>>,
+    /// Register words at the last stop.
+    last: StoredValue<Option<Vec<u16>>>,
+    /// Addresses written since the last stop.
+    dirty: StoredValue<HashSet<u16>>,
+}
+
+impl Default for ChangeSignals {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChangeSignals {
+    pub fn new() -> Self {
+        Self {
+            registers: RwSignal::new(HashMap::new()),
+            memory: RwSignal::new(HashMap::new()),
+            last: StoredValue::new(None),
+            dirty: StoredValue::new(HashSet::new()),
+        }
+    }
+
+    /// Remembers `registers` as the state of the last stop, unless there already is one.
+    pub fn baseline<R: RegisterState>(&self, registers: &R) {
+        if self.last.with_value(Option::is_none) {
+            self.last.set_value(Some(registers.to_words()));
+        }
+    }
+
+    pub fn written(&self, addresses: HashSet<u16>) {
+        if addresses.is_empty() {
+            return;
+        }
+        self.dirty.update_value(|dirty| dirty.extend(addresses));
+    }
+
+    /// Called whenever execution stops, compares the registers against the
+    /// previous stop and ages the older changes.
+    pub fn mark<R: RegisterState>(&self, registers: &R) {
+        let words = registers.to_words();
+        let changed: Vec<&'static str> = match self.last.get_value() {
+            Some(last) => R::WORD_NAMES
+                .iter()
+                .zip(words.iter().zip(last.iter()))
+                .filter(|(_, (now, before))| now != before)
+                .map(|(name, _)| *name)
+                .collect(),
+            None => Vec::new(),
+        };
+        self.last.set_value(Some(words));
+        let dirty = self
+            .dirty
+            .try_update_value(std::mem::take)
+            .unwrap_or_default();
+        self.registers.update(|ages| age(ages, changed));
+        self.memory.update(|ages| age(ages, dirty));
+    }
+
+    pub fn register_class(&self, name: &str) -> &'static str {
+        class(self.registers.with(|ages| ages.get(name).copied()))
+    }
+
+    pub fn memory_class(&self, address: u16) -> &'static str {
+        class(self.memory.with(|ages| ages.get(&address).copied()))
+    }
+}
+
+fn age<K: Hash + Eq>(ages: &mut HashMap<K, u8>, changed: impl IntoIterator<Item = K>) {
+    ages.retain(|_, age| {
+        *age += 1;
+        *age < FADE_STOPS
+    });
+    for key in changed {
+        ages.insert(key, 0);
+    }
+}
+
+fn class(age: Option<u8>) -> &'static str {
+    match age {
+        Some(0) => style::changed0,
+        Some(1) => style::changed1,
+        Some(_) => style::changed2,
+        None => "",
+    }
+}
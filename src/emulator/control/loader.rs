@@ -0,0 +1,241 @@
+use super::runner::RunnerSignals;
+use crate::emulator::cpu::UiCpu;
+use crate::emulator::files::{self, Image, ImageFormat};
+use crate::emulator::memory::map::MemoryMapSignals;
+use crate::emulator::{style, EmuSignals};
+use leptos::logging::warn;
+use leptos::prelude::*;
+use web_sys::{Event, HtmlInputElement};
+
+/// File picked in the dialog, kept until the user confirms the load.
+#[derive(Clone)]
+struct PendingFile {
+    name: String,
+    format: ImageFormat,
+    data: Vec<u8>,
+}
+
+fn parse_hex(value: &str) -> Result<usize, std::num::ParseIntError> {
+    usize::from_str_radix(value.trim().trim_start_matches("0x"), 16)
+}
+
+/// Hex input bound to `value`, rejecting anything above `max`.
+fn HexField(
+    title: &'static str,
+    value: RwSignal<Option<usize>>,
+    max: usize,
+    disabled: Signal<bool>,
+) -> impl IntoView {
+    view! {
+        <input
+            style:width="100%"
+            maxlength=5
+            title=title
+            placeholder=title
+            disabled=disabled
+            prop:value=move || value.get().map(|val| format!("{:04X}", val)).unwrap_or_default()
+            on:change=move |event| {
+                let text = event_target_value(&event);
+                if text.trim().is_empty() {
+                    value.set(None);
+                    return;
+                }
+                match parse_hex(&text) {
+                    Ok(val) if val <= max => value.set(Some(val)),
+                    _ => {
+                        warn!("Invalid hex value: {}", text);
+                        event_target::<HtmlInputElement>(&event)
+                            .set_value(
+                                &value
+                                    .get_untracked()
+                                    .map(|val| format!("{:04X}", val))
+                                    .unwrap_or_default(),
+                            );
+                    }
+                }
+            }
+        />
+    }
+}
+
+#[component]
+pub fn LoadDialog<C: UiCpu>(open: RwSignal<bool>) -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let runner = expect_context::<RunnerSignals<C>>();
+    let map_signals = expect_context::<MemoryMapSignals>();
+    let pending: RwSignal<Option<PendingFile>> = RwSignal::new(None);
+    let address = RwSignal::new(Some(0usize));
+    let offset = RwSignal::new(None);
+    let length = RwSignal::new(None);
+    let set_pc = RwSignal::new(false);
+    let force = RwSignal::new(false);
+    let status = RwSignal::new(String::new());
+
+    let is_binary = Signal::derive(move || {
+        pending.with(|file| file.as_ref().map(|file| file.format) == Some(ImageFormat::Binary))
+    });
+    let not_binary = Signal::derive(move || !is_binary.get());
+
+    let pick = move |event: Event| {
+        let element = event_target::<HtmlInputElement>(&event);
+        files::read_input_file(&element, move |name, data| {
+            let format = ImageFormat::detect(&data);
+            status.set(format!(
+                "Selected {} ({}, {} bytes)",
+                name,
+                format.name(),
+                data.len()
+            ));
+            pending.set(Some(PendingFile { name, format, data }));
+        });
+    };
+
+    let build_image = move |file: &PendingFile| -> Result<Image, String> {
+        match file.format {
+            ImageFormat::Binary => {
+                let start = offset.get_untracked().unwrap_or(0).min(file.data.len());
+                let end = length
+                    .get_untracked()
+                    .map(|length| (start + length).min(file.data.len()))
+                    .unwrap_or(file.data.len());
+                let address = address.get_untracked().unwrap_or(0) as u16;
+                let mut image = Image::binary(&file.data[start..end], address)?;
+                image.entry = Some(address);
+                Ok(image)
+            }
+            _ => Image::parse(&file.data).map(|(_, image)| image),
+        }
+    };
+
+    let load = move |_| {
+        let Some(file) = pending.get_untracked() else {
+            status.set("Select a file first".to_string());
+            return;
+        };
+        runner.stop();
+        let result = build_image(&file).and_then(|image| {
+            let mut result = Ok(());
+            emu_signals.write.update(|emu| {
+                result = image.write_to(&mut emu.memory, force.get_untracked());
+                if result.is_ok() && set_pc.get_untracked() {
+                    if let Some(entry) = image.entry {
+                        emu.cpu.set_pc(entry);
+                    }
+                }
+            });
+            result.map(|_| image)
+        });
+        match result {
+            Ok(image) => {
+                // A failed load leaves memory unchanged, so the history stays valid
+                runner.history.clear();
+                let devices = map_signals.read.with_untracked(|map| {
+                    let mut touched = image
+                        .segments
+                        .iter()
+                        .flat_map(|segment| {
+                            let start = segment.address as usize;
+                            map.touched(start..start + segment.data.len())
+                        })
+                        .collect::<Vec<_>>();
+                    // Segments are not necessarily in address order
+                    touched.sort_by_key(|region| region.start);
+                    touched.dedup();
+                    touched
+                        .iter()
+                        .map(|region| {
+                            format!(
+                                "{} {:04X}-{:04X}",
+                                region.name,
+                                region.start,
+                                region.end()
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                });
+                status.set(format!(
+                    "Loaded {} ({} bytes in {} segment(s)) into {}",
+                    file.name,
+                    image.size(),
+                    image.segments.len(),
+                    devices
+                ));
+                open.set(false);
+            }
+            Err(err) => {
+                warn!("Failed to load {}: {}", file.name, err);
+                status.set(format!("Failed to load {}: {}", file.name, err));
+            }
+        }
+    };
+
+    view! {
+        <Show when=move || open.get()>
+            <tr>
+                <th class=style::tablebutton colspan=2>
+                    <input
+                        on:change=pick
+                        type="file"
+                        style:display="none"
+                        id="load-dialog-input"
+                    />
+                    <label for="load-dialog-input" style:padding="0.3rem">
+                        "Choose file"
+                    </label>
+                </th>
+                <th class=style::tablecell>
+                    {HexField("Address", address, 0xFFFF, not_binary)}
+                </th>
+                <th class=style::tablecell>
+                    {HexField("Offset", offset, usize::MAX, not_binary)}
+                </th>
+                <th class=style::tablecell>
+                    {HexField("Length", length, 0x10000, not_binary)}
+                </th>
+                <th class=style::tablecell rowspan=2 colspan=2>
+                    <label style:display="block">
+                        <input
+                            type="checkbox"
+                            prop:checked=set_pc
+                            on:change=move |event| set_pc.set(event_target_checked(&event))
+                        />
+                        " Set PC to entry"
+                    </label>
+                    <label style:display="block">
+                        <input
+                            type="checkbox"
+                            prop:checked=force
+                            on:change=move |event| force.set(event_target_checked(&event))
+                        />
+                        " Write read-only"
+                    </label>
+                </th>
+            </tr>
+            <tr>
+                <th class=style::tableleft colspan=3 style:padding="0.3rem">
+                    <span>
+                        {move || {
+                            pending
+                                .with(|file| {
+                                    file.as_ref()
+                                        .map(|file| file.name.clone())
+                                        .unwrap_or_else(|| "No file selected".to_string())
+                                })
+                        }}
+                    </span>
+                </th>
+                <th class=style::tablebutton colspan=2 style:padding="0.3rem" on:click=load>
+                    "Load"
+                </th>
+            </tr>
+        </Show>
+        <Show when=move || !status.get().is_empty()>
+            <tr>
+                <th class=style::tableleft colspan=7 style:padding="0.3rem">
+                    <span>{move || status.get()}</span>
+                </th>
+            </tr>
+        </Show>
+    }
+}
@@ -1,18 +1,176 @@
+use super::cpu::UiCpu;
+use super::files::{self, ImageFormat};
 use super::{style, EmuSignals};
-use emu_lib::cpu::instruction::ExecutableInstruction;
 use emu_lib::cpu::Cpu;
-use emu_lib::emulator::Emulator;
-use leptos::logging::log;
+use leptos::logging::warn;
 use leptos::prelude::*;
-use tokio::time::Duration;
-use web_sys::wasm_bindgen::closure::Closure;
-use web_sys::wasm_bindgen::JsCast;
+use loader::LoadDialog;
+use runner::{ClockSpeed, RunnerSignals};
 use web_sys::HtmlInputElement;
-use web_sys::{js_sys, MouseEvent};
 
-#[island]
-pub fn Control() -> impl IntoView {
-    let emu_signals = expect_context::<EmuSignals>();
+pub mod loader;
+pub mod runner;
+pub mod stepping;
+
+#[component]
+pub fn ClockControl<C: UiCpu>() -> impl IntoView {
+    let runner = expect_context::<RunnerSignals<C>>();
+    let preset_value = move || {
+        ClockSpeed::PRESETS
+            .iter()
+            .position(|(_, speed)| *speed == runner.clock.get())
+            .map(|index| index.to_string())
+            .unwrap_or_else(|| "custom".to_string())
+    };
+    let slider_value = move || {
+        runner
+            .clock
+            .get()
+            .mhz()
+            .map(|mhz| mhz.to_string())
+            .unwrap_or_else(|| "20".to_string())
+    };
+    let measured = move || match runner.active.get() {
+        true => format!("{:.3} MHz", runner.measured_hz.get() / 1_000_000.0),
+        false => "-".to_string(),
+    };
+    view! {
+        <tr>
+            <th class=style::tabletop style:padding="0.3rem">
+                <span>"Clock"</span>
+            </th>
+            <th class=style::tablecell>
+                <select
+                    style:width="100%"
+                    prop:value=preset_value
+                    on:change=move |event| {
+                        if let Ok(index) = event_target_value(&event).parse::<usize>() {
+                            if let Some((_, speed)) = ClockSpeed::PRESETS.get(index) {
+                                runner.clock.set(*speed);
+                            }
+                        }
+                    }
+                >
+                    {ClockSpeed::PRESETS
+                        .iter()
+                        .enumerate()
+                        .map(|(index, (name, _))| {
+                            view! { <option value=index.to_string()>{*name}</option> }
+                        })
+                        .collect_view()}
+                    <option value="custom" disabled=true>
+                        "Custom"
+                    </option>
+                </select>
+            </th>
+            <th class=style::tablecell colspan=4>
+                <input
+                    type="range"
+                    min="0.1"
+                    max="20"
+                    step="0.1"
+                    style:width="100%"
+                    prop:value=slider_value
+                    on:input=move |event| {
+                        if let Ok(mhz) = event_target_value(&event).parse::<f64>() {
+                            runner.clock.set(ClockSpeed::Hz(mhz * 1_000_000.0));
+                        }
+                    }
+                />
+            </th>
+            <th class=style::tabletop style:padding="0.3rem">
+                <span>{measured}</span>
+            </th>
+        </tr>
+    }
+}
+
+#[component]
+pub fn ExportControl<C: UiCpu>() -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let start = RwSignal::new(0u16);
+    let length = RwSignal::new(0x1000usize);
+    let format = RwSignal::new(ImageFormat::IntelHex);
+    let export = move |_| {
+        let segment = emu_signals.read.with_untracked(|emu| {
+            files::read_segment(&emu.memory, start.get_untracked(), length.get_untracked())
+        });
+        let format = format.get_untracked();
+        let text = match format {
+            ImageFormat::SRecord => files::srec::export(&segment),
+            _ => files::ihex::export(&segment),
+        };
+        let name = format!("memory_{:04X}.{}", segment.address, format.extension());
+        files::download_text(&name, &text);
+    };
+    view! {
+        <tr>
+            <th class=style::tabletop style:padding="0.3rem">
+                <span>"Export"</span>
+            </th>
+            <th class=style::tablecell>
+                <input
+                    style:width="100%"
+                    maxlength=4
+                    title="Start address"
+                    prop:value=move || format!("{:04X}", start.get())
+                    on:change=move |event| {
+                        match u16::from_str_radix(&event_target_value(&event), 16) {
+                            Ok(val) => start.set(val),
+                            Err(_) => {
+                                warn!("Invalid hex value");
+                                event_target::<HtmlInputElement>(&event)
+                                    .set_value(&format!("{:04X}", start.get_untracked()));
+                            }
+                        }
+                    }
+                />
+            </th>
+            <th class=style::tablecell colspan=2>
+                <input
+                    style:width="100%"
+                    maxlength=5
+                    title="Length"
+                    prop:value=move || format!("{:04X}", length.get())
+                    on:change=move |event| {
+                        match usize::from_str_radix(&event_target_value(&event), 16) {
+                            Ok(val) if val <= 0x10000 => length.set(val),
+                            _ => {
+                                warn!("Invalid hex value");
+                                event_target::<HtmlInputElement>(&event)
+                                    .set_value(&format!("{:04X}", length.get_untracked()));
+                            }
+                        }
+                    }
+                />
+            </th>
+            <th class=style::tablecell colspan=2>
+                <select
+                    style:width="100%"
+                    on:change=move |event| {
+                        format
+                            .set(
+                                match event_target_value(&event).as_str() {
+                                    "s19" => ImageFormat::SRecord,
+                                    _ => ImageFormat::IntelHex,
+                                },
+                            );
+                    }
+                >
+                    <option value="hex">"Intel HEX"</option>
+                    <option value="s19">"S-record"</option>
+                </select>
+            </th>
+            <th class=style::tablebutton style:padding="0.3rem" on:click=export>
+                "Export"
+            </th>
+        </tr>
+    }
+}
+
+#[component]
+pub fn Control<C: UiCpu>() -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
     let halted_class = move || {
         emu_signals.read.with(|emu| match emu.cpu.halted() {
             true => style::tablebuttoninvert,
@@ -25,68 +183,18 @@ pub fn Control() -> impl IntoView {
         });
     };
 
-    let runner_active: RwSignal<Option<IntervalHandle>> = RwSignal::new(None);
-    let runner_class = move || match runner_active.get() {
-        Some(_) => style::tablebuttoninvert,
-        None => style::tablebutton,
-    };
-    let switch_runner = move || {
-        runner_active.update(|runner_state| match runner_state {
-            Some(handle) => {
-                handle.clear();
-                *runner_state = None;
-            }
-            None => {
-                let interval_result = set_interval_with_handle(
-                    move || {
-                        emu_signals.write.update(|emu| match emu.run_ticks::<_>(400000.0,&Some(|_emu:&mut Emulator<_>,_ins:&dyn ExecutableInstruction<_>|{})) {
-                            Ok(_) => {}
-                            Err(err) => {
-                                runner_active.update(|runner_active| {
-                                    if let Some(handle) = runner_active {
-                                        handle.clear();
-                                    }
-                                    *runner_active = None;
-                                });
-                                warn!("Running stopper due to an error:{:?}", err)
-                            }
-                        });
-                    },
-                    Duration::from_millis(0),
-                );
-                match interval_result {
-                    Ok(interval) => {
-                        *runner_state = Some(interval);
-                    }
-                    Err(err) => {
-                        warn!("Running stopper due to an error:{:?}", err);
-                    }
-                }
-            }
-        });
+    let runner = expect_context::<RunnerSignals<C>>();
+    on_cleanup(move || runner.stop());
+    let runner_class = move || match runner.active.get() {
+        true => style::tablebuttoninvert,
+        false => style::tablebutton,
     };
+    let switch_runner = move || runner.toggle(emu_signals);
 
-    let file_event = move |event: MouseEvent| {
-        let element = event_target::<HtmlInputElement>(&event);
-        if let Some(files) = element.files() {
-            if let Some(file) = files.get(0) {
-                log!("Loading file: {:?}", file);
-                let reader = web_sys::FileReader::new().unwrap();
-                let reader_clone = reader.clone();
-                let onloadend_callback = Closure::wrap(Box::new(move || {
-                    let array_buffer = reader_clone.result().unwrap();
-                    let uint8_array = js_sys::Uint8Array::new(&array_buffer);
-                    let file_content = uint8_array.to_vec();
-                    log!("Loaded file with {} bytes", file_content.len());
-                    emu_signals.write.update(|emu| {
-                        emu.memory.load(&file_content).unwrap();
-                    });
-                }) as Box<dyn FnMut()>);
-                reader.set_onloadend(Some(onloadend_callback.as_ref().unchecked_ref()));
-                reader.read_as_array_buffer(&file).unwrap();
-                onloadend_callback.forget();
-            }
-        }
+    let load_open = RwSignal::new(false);
+    let load_class = move || match load_open.get() {
+        true => style::tablebuttoninvert,
+        false => style::tablebutton,
     };
 
     view! {
@@ -96,20 +204,34 @@ pub fn Control() -> impl IntoView {
                     class=style::tablebutton
                     style:padding="0.3rem"
                     on:click=move |_| {
-                        emu_signals
-                            .write
-                            .update(|emu| {
-                                match emu.step() {
-                                    Ok(_) => {}
-                                    Err(e) => {
-                                        log::error!("Error stepping: {}", e);
-                                    }
-                                }
-                            });
+                        runner.stop();
+                        runner.history.step_back(emu_signals);
+                        runner.settle(emu_signals);
                     }
+                >
+                    "Step Back"
+                </th>
+                <th
+                    class=style::tablebutton
+                    style:padding="0.3rem"
+                    on:click=move |_| stepping::step(emu_signals, runner)
                 >
                     Step
                 </th>
+                <th
+                    class=style::tablebutton
+                    style:padding="0.3rem"
+                    on:click=move |_| stepping::step_over(emu_signals, runner)
+                >
+                    "Step Over"
+                </th>
+                <th
+                    class=style::tablebutton
+                    style:padding="0.3rem"
+                    on:click=move |_| stepping::step_out(emu_signals, runner)
+                >
+                    "Step Out"
+                </th>
                 <th
                     class=move || runner_class
                     on:click=move |_| {
@@ -128,19 +250,17 @@ pub fn Control() -> impl IntoView {
                 >
                     Halted
                 </th>
-                <th class=style::tablebutton>
-                    <input
-                        on:click=move |e| file_event(e)
-                        value="SAFA"
-                        type="file"
-                        style:display="none"
-                        id="file-input"
-                    />
-                    <label for="file-input" style:padding="0.3rem">
-                        Load file
-                    </label>
+                <th
+                    class=load_class
+                    style:padding="0.3rem"
+                    on:click=move |_| load_open.update(|open| *open = !*open)
+                >
+                    Load file
                 </th>
             </tr>
+            <ClockControl<C> />
+            <ExportControl<C> />
+            <LoadDialog<C> open=load_open />
         </table>
     }
 }
@@ -0,0 +1,447 @@
+use super::stepping;
+use crate::emulator::breakpoints::{BreakAction, BreakpointChecker, BreakpointSignals};
+use crate::emulator::changes::ChangeSignals;
+use crate::emulator::cpu::{RegisterState, UiCpu};
+use crate::emulator::heatmap::{HeatShared, HeatSignals};
+use crate::emulator::history::{HistorySignals, Journal};
+use crate::emulator::stats::StatsSignals;
+use crate::emulator::trace::{TraceEntry, TraceSignals};
+use crate::emulator::watch::{WatchHit, WatchShared, WatchSignals};
+use crate::emulator::EmuSignals;
+use emu_lib::cpu::instruction::ExecutableInstruction;
+use emu_lib::cpu::Cpu;
+use emu_lib::emulator::Emulator;
+use leptos::logging::warn;
+use leptos::prelude::*;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Longest wall-clock gap a single frame is allowed to catch up on.
+/// Anything above this (background tab, debugger pause) is dropped instead of
+/// being replayed in one huge burst.
+const MAX_FRAME_MS: f64 = 250.0;
+/// Host time spent emulating per frame when running unthrottled.
+const UNLIMITED_FRAME_MS: f64 = 12.0;
+/// T-states executed between host clock checks when running unthrottled.
+const UNLIMITED_CHUNK: f64 = 20_000.0;
+/// How often the measured clock readout is refreshed.
+const MEASURE_WINDOW_MS: f64 = 500.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ClockSpeed {
+    Hz(f64),
+    Unlimited,
+}
+
+impl ClockSpeed {
+    pub const PRESETS: [(&'static str, ClockSpeed); 4] = [
+        ("2 MHz", ClockSpeed::Hz(2_000_000.0)),
+        ("3.5 MHz", ClockSpeed::Hz(3_500_000.0)),
+        ("4 MHz", ClockSpeed::Hz(4_000_000.0)),
+        ("Unlimited", ClockSpeed::Unlimited),
+    ];
+
+    pub fn mhz(&self) -> Option<f64> {
+        match self {
+            ClockSpeed::Hz(hz) => Some(hz / 1_000_000.0),
+            ClockSpeed::Unlimited => None,
+        }
+    }
+}
+
+impl Default for ClockSpeed {
+    fn default() -> Self {
+        ClockSpeed::Hz(3_500_000.0)
+    }
+}
+
+/// Where a targeted run (step over, step out, run to cursor) should stop.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RunTarget {
+    /// Stop once PC reaches `address` with the stack no deeper than `sp`.
+    Address { address: u16, sp: u16 },
+    /// Stop after a RET pops the frame that was current when `sp` was recorded.
+    Return { sp: u16 },
+}
+
+impl RunTarget {
+    fn reached<C: UiCpu>(
+        &self,
+        registers: &C::Registers,
+        ins: &dyn ExecutableInstruction<C>,
+    ) -> bool {
+        let pc = registers.pc();
+        let sp = registers.sp();
+        match *self {
+            RunTarget::Address {
+                address,
+                sp: frame_sp,
+            } => pc == address && sp >= frame_sp,
+            RunTarget::Return { sp: frame_sp } => {
+                stepping::is_return::<C>(&ins.to_string()) && sp > frame_sp
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct FrameState {
+    last: f64,
+    debt: f64,
+    window_start: f64,
+    window_ticks: f64,
+}
+
+pub struct RunnerSignals<C: UiCpu> {
+    pub active: RwSignal<bool>,
+    pub clock: RwSignal<ClockSpeed>,
+    pub measured_hz: RwSignal<f64>,
+    pub target: RwSignal<Option<RunTarget>>,
+    pub history: HistorySignals<C::Registers>,
+    pub breakpoints: BreakpointSignals,
+    pub watches: WatchSignals,
+    pub heat: HeatSignals,
+    pub trace: TraceSignals<C::Registers>,
+    pub stats: StatsSignals,
+    pub changes: ChangeSignals,
+    handle: StoredValue<Option<AnimationFrameRequestHandle>>,
+    frame: StoredValue<FrameState>,
+}
+
+// Derive would bound `C: Copy`, although only `C::Registers` appears in the fields
+impl<C: UiCpu> Clone for RunnerSignals<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C: UiCpu> Copy for RunnerSignals<C> {}
+
+impl<C: UiCpu> Default for RunnerSignals<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: UiCpu> RunnerSignals<C> {
+    /// Must be created after the contexts of every instruction hook are provided.
+    pub fn new() -> Self {
+        Self {
+            active: RwSignal::new(false),
+            clock: RwSignal::new(ClockSpeed::default()),
+            measured_hz: RwSignal::new(0.0),
+            target: RwSignal::new(None),
+            history: expect_context::<HistorySignals<C::Registers>>(),
+            breakpoints: expect_context::<BreakpointSignals>(),
+            watches: expect_context::<WatchSignals>(),
+            heat: expect_context::<HeatSignals>(),
+            trace: expect_context::<TraceSignals<C::Registers>>(),
+            stats: expect_context::<StatsSignals>(),
+            changes: expect_context::<ChangeSignals>(),
+            handle: StoredValue::new(None),
+            frame: StoredValue::new(FrameState::default()),
+        }
+    }
+
+    pub fn start(&self, emu_signals: EmuSignals<C>) {
+        self.start_until(emu_signals, None);
+    }
+
+    /// Starts running and stops on its own once `target` is reached.
+    pub fn run_to(&self, emu_signals: EmuSignals<C>, target: RunTarget) {
+        self.stop();
+        self.start_until(emu_signals, Some(target));
+    }
+
+    fn start_until(&self, emu_signals: EmuSignals<C>, target: Option<RunTarget>) {
+        if self.active.get_untracked() {
+            return;
+        }
+        self.target.set(target);
+        let now = now_ms();
+        self.frame.set_value(FrameState {
+            last: now,
+            window_start: now,
+            ..Default::default()
+        });
+        self.active.set(true);
+        self.schedule(emu_signals);
+    }
+
+    pub fn stop(&self) {
+        if let Some(handle) = self.handle.get_value() {
+            handle.cancel();
+        }
+        self.handle.set_value(None);
+        self.target.set(None);
+        self.active.set(false);
+        self.measured_hz.set(0.0);
+    }
+
+    pub fn toggle(&self, emu_signals: EmuSignals<C>) {
+        match self.active.get_untracked() {
+            true => {
+                self.stop();
+                self.settle(emu_signals);
+            }
+            false => self.start(emu_signals),
+        }
+    }
+
+    /// Marks the current state as a stop, for highlighting what changed since the last one.
+    pub fn settle(&self, emu_signals: EmuSignals<C>) {
+        let registers = emu_signals
+            .read
+            .with_untracked(|emu| emu.cpu.capture_registers());
+        self.changes.mark(&registers);
+    }
+
+    fn schedule(&self, emu_signals: EmuSignals<C>) {
+        let runner = *self;
+        match request_animation_frame_with_handle(move || runner.frame(emu_signals)) {
+            Ok(handle) => self.handle.set_value(Some(handle)),
+            Err(err) => {
+                warn!("Running stopped due to an error:{:?}", err);
+                self.stop();
+            }
+        }
+    }
+
+    fn frame(&self, emu_signals: EmuSignals<C>) {
+        if !self.active.get_untracked() {
+            return;
+        }
+        let start = now_ms();
+        let state = self.frame.get_value();
+        let elapsed = (start - state.last).clamp(0.0, MAX_FRAME_MS);
+        let target = self.target.get_untracked();
+        // Targeted runs are debugger commands, they should finish as fast as possible.
+        let clock = match target {
+            Some(_) => ClockSpeed::Unlimited,
+            None => self.clock.get_untracked(),
+        };
+
+        let mut stopped = false;
+        let mut executed = 0.0;
+        emu_signals.write.update(|emu| {
+            match clock {
+                ClockSpeed::Hz(hz) => {
+                    let budget = state.debt + hz * elapsed / 1000.0;
+                    if budget >= 1.0 {
+                        match self.run_budget(emu, budget, target) {
+                            Ok(ran) => {
+                                executed = ran;
+                                stopped = ran < budget;
+                            }
+                            Err(err) => {
+                                warn!("Running stopped due to an error:{:?}", err);
+                                stopped = true;
+                            }
+                        }
+                    }
+                }
+                ClockSpeed::Unlimited => {
+                    while !stopped && now_ms() - start < UNLIMITED_FRAME_MS {
+                        match self.run_budget(emu, UNLIMITED_CHUNK, target) {
+                            Ok(ran) => {
+                                executed += ran;
+                                stopped = ran < UNLIMITED_CHUNK;
+                            }
+                            Err(err) => {
+                                warn!("Running stopped due to an error:{:?}", err);
+                                stopped = true;
+                            }
+                        }
+                    }
+                }
+            }
+            stopped |= emu.cpu.halted();
+            let pc = *emu.cpu.registers().pc;
+            if stopped && target.is_none() && self.breakpoints.contains(emu, pc) {
+                self.stats.breakpoint_hit();
+            }
+        });
+        self.history.sync();
+
+        let mut state = state;
+        state.last = start;
+        state.debt = match clock {
+            // Carry the remainder (or the overshoot of the last instruction)
+            // into the next frame so the long-run rate matches the clock.
+            ClockSpeed::Hz(hz) if !stopped => state.debt + hz * elapsed / 1000.0 - executed,
+            _ => 0.0,
+        };
+        state.window_ticks += executed;
+        if start - state.window_start >= MEASURE_WINDOW_MS {
+            self.measured_hz
+                .set(state.window_ticks * 1000.0 / (start - state.window_start));
+            state.window_start = start;
+            state.window_ticks = 0.0;
+        }
+        self.frame.set_value(state);
+        self.stats.frame_ms.set(now_ms() - start);
+
+        match stopped {
+            true => {
+                self.stop();
+                self.settle(emu_signals);
+            }
+            false => self.schedule(emu_signals),
+        }
+    }
+}
+
+/// Everything that observes executed instructions during one run.
+struct Hooks<'a, C: UiCpu> {
+    journal: &'a mut Journal<C::Registers>,
+    breakpoints: BreakpointChecker,
+    watches: Arc<WatchShared>,
+    watching: bool,
+    watch_hits: Vec<WatchHit>,
+    heat: Arc<HeatShared>,
+    /// Addresses written by the executed instructions.
+    written: HashSet<u16>,
+    trace: Option<Vec<TraceEntry<C::Registers>>>,
+    target: Option<RunTarget>,
+    /// Registers before the instruction being executed.
+    before: C::Registers,
+    executed: f64,
+    instructions: u64,
+    /// Set when a hook halted the cpu to stop, holds the halted state it had before.
+    stop_requested: Option<bool>,
+}
+
+impl<C: UiCpu> Hooks<'_, C> {
+    fn after(&mut self, emu: &mut Emulator<C>, ins: &dyn ExecutableInstruction<C>) {
+        // Memory read by the hooks themselves must not trigger watchpoints
+        // or count as accesses
+        self.watches.disarm();
+        self.heat.disarm();
+        self.heat.executed(self.before.pc(), ins.common().length as u16);
+        let hits = self.watches.take_hits(self.before.pc());
+        let mut stop = !hits.is_empty();
+        self.watch_hits.extend(hits);
+        self.executed += ins.common().cycles as f64;
+        self.instructions += 1;
+        self.journal.record(emu, &mut self.written);
+        let after = emu.cpu.capture_registers();
+        stop |= self
+            .target
+            .is_some_and(|target| target.reached::<C>(&after, ins));
+        if let BreakAction::Stop(_) = self.breakpoints.check(emu) {
+            stop = true;
+        }
+        if let Some(trace) = &mut self.trace {
+            trace.push(TraceEntry {
+                pc: self.before.pc(),
+                bytes: ins.to_bytes(),
+                asm: ins.to_string(),
+                registers: self.before,
+            });
+        }
+        self.before = after;
+        if self.watching {
+            self.watches.resume();
+        }
+        self.heat.arm();
+        if stop {
+            // run_ticks can only be interrupted by halting the cpu,
+            // the halt is undone as soon as it returns.
+            self.stop_requested.get_or_insert(emu.cpu.halted());
+            emu.cpu.set_halted(true);
+        }
+    }
+}
+
+impl<C: UiCpu> RunnerSignals<C> {
+    /// Calls `run` with every instruction hook attached and returns its
+    /// result along with the number of T-states executed.
+    fn observe<R>(
+        &self,
+        emu: &mut Emulator<C>,
+        target: Option<RunTarget>,
+        run: impl FnOnce(&mut Emulator<C>, &RefCell<Hooks<C>>) -> R,
+    ) -> (R, f64) {
+        self.changes.baseline(&emu.cpu.capture_registers());
+        let watches = self.watches.shared.get_value();
+        let watching = watches.arm();
+        let heat = self.heat.shared.get_value();
+        heat.arm();
+        let mut output = None;
+        self.history.journal.update_value(|journal| {
+            journal.begin(emu);
+            let hooks = RefCell::new(Hooks {
+                journal,
+                breakpoints: self.breakpoints.checker(),
+                watches: watches.clone(),
+                watching,
+                watch_hits: Vec::new(),
+                heat: heat.clone(),
+                written: HashSet::new(),
+                trace: self.trace.enabled.get_untracked().then(Vec::new),
+                target,
+                before: emu.cpu.capture_registers(),
+                executed: 0.0,
+                instructions: 0,
+                stop_requested: None,
+            });
+            let result = run(emu, &hooks);
+            output = Some((result, hooks.into_inner()));
+        });
+        watches.disarm();
+        heat.disarm();
+        let (result, hooks) = output.unwrap();
+        if let Some(halted) = hooks.stop_requested {
+            emu.cpu.set_halted(halted);
+        }
+        self.breakpoints.commit(hooks.breakpoints);
+        self.watches.report(hooks.watch_hits);
+        if let Some(trace) = hooks.trace {
+            self.trace.record(trace);
+        }
+        self.stats.add(hooks.executed as u64, hooks.instructions);
+        self.changes.written(hooks.written);
+        (result, hooks.executed)
+    }
+
+    /// Runs the emulator for roughly `ticks` T-states and returns how many were
+    /// actually executed. Fewer than requested means the emulator stopped early
+    /// (breakpoint, watchpoint, halt or `target` reached).
+    fn run_budget(
+        &self,
+        emu: &mut Emulator<C>,
+        ticks: f64,
+        target: Option<RunTarget>,
+    ) -> Result<f64, String> {
+        let (result, executed) = self.observe(emu, target, |emu, hooks| {
+            let callback = |emu: &mut Emulator<C>, ins: &dyn ExecutableInstruction<C>| {
+                hooks.borrow_mut().after(emu, ins);
+            };
+            emu.run_ticks(ticks, &Some(callback))
+                .map(|_| ())
+                .map_err(|err| format!("{:?}", err))
+        });
+        result?;
+        Ok(executed)
+    }
+
+    /// Executes a single instruction with every instruction hook attached.
+    pub fn step(&self, emu: &mut Emulator<C>) -> Result<(), String> {
+        let pc = *emu.cpu.registers().pc;
+        let ins = emu.cpu.parser().ins_from_mem(&emu.memory, pc)?;
+        let (result, _) = self.observe(emu, None, |emu, hooks| {
+            emu.step().map_err(|err| format!("{}", err))?;
+            hooks.borrow_mut().after(emu, ins.as_ref());
+            Ok(())
+        });
+        result
+    }
+}
+
+pub fn now_ms() -> f64 {
+    window()
+        .performance()
+        .map(|performance| performance.now())
+        .unwrap_or_else(web_sys::js_sys::Date::now)
+}
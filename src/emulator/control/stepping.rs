@@ -0,0 +1,61 @@
+use super::runner::{RunTarget, RunnerSignals};
+use crate::emulator::cpu::{RegisterState, UiCpu};
+use crate::emulator::EmuSignals;
+use emu_lib::cpu::Cpu;
+use leptos::logging::error;
+use leptos::prelude::*;
+
+fn mnemonic(asm: &str) -> String {
+    asm.split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_ascii_uppercase()
+}
+
+pub fn is_step_over<C: UiCpu>(asm: &str) -> bool {
+    C::STEP_OVER_MNEMONICS.contains(&mnemonic(asm).as_str())
+}
+
+pub fn is_return<C: UiCpu>(asm: &str) -> bool {
+    C::RETURN_MNEMONICS.contains(&mnemonic(asm).as_str())
+}
+
+pub fn step<C: UiCpu>(emu_signals: EmuSignals<C>, runner: RunnerSignals<C>) {
+    emu_signals.write.update(|emu| {
+        if let Err(e) = runner.step(emu) {
+            error!("Error stepping: {}", e);
+        }
+    });
+    runner.history.sync();
+    runner.settle(emu_signals);
+}
+
+/// Steps one instruction, running subroutine calls and repeating
+/// instructions to completion as if they were a single step.
+pub fn step_over<C: UiCpu>(emu_signals: EmuSignals<C>, runner: RunnerSignals<C>) {
+    let target = emu_signals.read.with_untracked(|emu| {
+        let registers = emu.cpu.capture_registers();
+        let pc = registers.pc();
+        let ins = emu.cpu.parser().ins_from_mem(&emu.memory, pc).ok()?;
+        is_step_over::<C>(&ins.to_string()).then(|| RunTarget::Address {
+            address: pc.wrapping_add(ins.common().length as u16),
+            sp: registers.sp(),
+        })
+    });
+    match target {
+        Some(target) => runner.run_to(emu_signals, target),
+        None => step(emu_signals, runner),
+    }
+}
+
+/// Runs until the current subroutine returns to its caller.
+pub fn step_out<C: UiCpu>(emu_signals: EmuSignals<C>, runner: RunnerSignals<C>) {
+    let sp = emu_signals
+        .read
+        .with_untracked(|emu| emu.cpu.capture_registers().sp());
+    runner.run_to(emu_signals, RunTarget::Return { sp });
+}
+
+pub fn run_to_cursor<C: UiCpu>(emu_signals: EmuSignals<C>, runner: RunnerSignals<C>, address: u16) {
+    runner.run_to(emu_signals, RunTarget::Address { address, sp: 0 });
+}
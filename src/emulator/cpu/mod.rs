@@ -0,0 +1,72 @@
+use emu_lib::cpu::i8080::I8080;
+use emu_lib::cpu::z80::Z80;
+use emu_lib::cpu::Cpu;
+use leptos::tachys::view::any_view::AnyView;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::hash::Hash;
+
+/// A flag bit of F, from bit 7 down to bit 0, with the condition mnemonics
+/// for the set and clear states where the bit has one, and the value of bits
+/// the cpu keeps fixed.
+pub type FlagLayout = [(
+    &'static str,
+    Option<(&'static str, &'static str)>,
+    Option<bool>,
+); 8];
+
+/// Copy of a cpu's register file, used to rewind, trace or compare cpu state.
+pub trait RegisterState:
+    Copy + PartialEq + Eq + Hash + Debug + Default + Serialize + Send + Sync + 'static
+{
+    /// Name of the cpu, stored in snapshots so they are only restored on the same cpu.
+    const CPU: &'static str;
+    /// Names of the `to_words` entries, as shown in the register panel.
+    const WORD_NAMES: &'static [&'static str];
+
+    fn pc(&self) -> u16;
+    fn sp(&self) -> u16;
+    /// Register by the name used in breakpoint expressions ("AF", "HL'", "IX", "I", ...),
+    /// `None` when this cpu does not have it.
+    fn get(&self, name: &str) -> Option<u16>;
+    /// Registers shown in trace lines, as (name, value, hex digits).
+    fn columns(&self) -> Vec<(&'static str, u16, usize)>;
+    /// Every field of the register file, in a fixed order, for snapshots.
+    fn to_words(&self) -> Vec<u16>;
+    fn from_words(words: &[u16]) -> Option<Self>;
+}
+
+/// Everything the UI needs to know about a cpu beyond `emu_lib::cpu::Cpu`.
+pub trait UiCpu: Cpu + Send + Sync + 'static {
+    type Registers: RegisterState;
+    const FLAGS: FlagLayout;
+    /// Instructions that may run for a long time (or through a subroutine)
+    /// before reaching the next instruction in memory.
+    const STEP_OVER_MNEMONICS: &'static [&'static str];
+    const RETURN_MNEMONICS: &'static [&'static str];
+
+    fn capture_registers(&self) -> Self::Registers;
+    fn restore_registers(&mut self, registers: &Self::Registers);
+    fn set_pc(&mut self, pc: u16);
+    fn flags(&self) -> u8;
+    fn set_flags(&mut self, flags: u8);
+    fn registers_panel() -> AnyView;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CpuKind {
+    #[default]
+    Z80,
+    I8080,
+}
+
+impl CpuKind {
+    pub const ALL: [CpuKind; 2] = [CpuKind::Z80, CpuKind::I8080];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CpuKind::Z80 => <<Z80 as UiCpu>::Registers as RegisterState>::CPU,
+            CpuKind::I8080 => <<I8080 as UiCpu>::Registers as RegisterState>::CPU,
+        }
+    }
+}
@@ -1,3 +1,7 @@
+use super::breakpoints::BreakpointSignals;
+use super::control::runner::RunnerSignals;
+use super::control::stepping;
+use super::cpu::UiCpu;
 use super::{style, EmuSignals};
 use emu_lib::cpu::Cpu;
 use emu_lib::memory::MemoryDevice;
@@ -7,8 +11,8 @@ use stylance::classes;
 use web_sys::wasm_bindgen::JsCast;
 
 #[component]
-pub fn FollowPCSwitch() -> impl IntoView {
-    let emu_signals = expect_context::<EmuSignals>();
+pub fn FollowPCSwitch<C: UiCpu>() -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
     let start_pos_signals = expect_context::<StartPosSignals>();
     let elem_class = move || match start_pos_signals.read.get() {
         Some(_) => style::tablebutton,
@@ -16,7 +20,7 @@ pub fn FollowPCSwitch() -> impl IntoView {
     };
     view! {
         <tr>
-            <td colspan=4>
+            <td colspan=5>
                 <div style:display="flex">
                     <button
                         class=elem_class
@@ -95,31 +99,28 @@ pub fn DisasmThead() -> impl IntoView {
             <th class=style::tabletop>
                 <span>"Asm"</span>
             </th>
+            <th class=style::tabletop>
+                <span>"Go"</span>
+            </th>
         </tr>
     }
 }
 
 #[component]
-pub fn DisasmTr(
+pub fn DisasmTr<C: UiCpu>(
     address: u16,
     // instruction: Result<Box<dyn emu_lib::cpu::instruction::ExecutableInstruction<Z80>>, String>,
     instruction: Option<(String, String)>,
 ) -> impl IntoView {
-    let emu_signals = expect_context::<EmuSignals>();
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let runner = expect_context::<RunnerSignals<C>>();
+    let breakpoints = expect_context::<BreakpointSignals>();
     let class_is_bk = move || {
         emu_signals
             .read
-            .with(|emu| emu.breakpoints.contains(&address))
-    };
-    let switch_bk = move |_| {
-        emu_signals.write.update(|emu| {
-            if emu.breakpoints.contains(&address) {
-                emu.breakpoints.retain(|&x| x != address);
-            } else {
-                emu.breakpoints.push(address);
-            }
-        });
+            .with(|emu| breakpoints.contains(emu, address))
     };
+    let switch_bk = move |_| breakpoints.toggle(emu_signals, address);
     let class_is_pc = move || match emu_signals
         .read
         .with(|emu| *emu.cpu.registers().pc == address)
@@ -172,13 +173,20 @@ pub fn DisasmTr(
                         .into_any()
                 }
             }}
+            <td
+                class=style::tablebutton
+                title="Run to cursor"
+                on:click=move |_| stepping::run_to_cursor(emu_signals, runner, address)
+            >
+                <span>"▶"</span>
+            </td>
         </tr>
     }
 }
 
 #[component]
-pub fn DisasmTbody(rows: usize) -> impl IntoView {
-    let emu_signals = expect_context::<EmuSignals>();
+pub fn DisasmTbody<C: UiCpu>(rows: usize) -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
     let start_pos_signals = expect_context::<StartPosSignals>();
     view! {
         <tbody>
@@ -217,7 +225,7 @@ pub fn DisasmTbody(rows: usize) -> impl IntoView {
                             };
                             pc += size;
                             view! {
-                                <DisasmTr
+                                <DisasmTr<C>
                                     address=(pc - size) as u16
                                     instruction=string_instruction
                                 />
@@ -231,26 +239,40 @@ pub fn DisasmTbody(rows: usize) -> impl IntoView {
     }
 }
 
-#[derive(Clone)]
+/// First address shown by the disassembler, `None` while following PC.
+/// Provided by the emulator so other panels can jump it to an address.
+#[derive(Clone, Copy)]
 pub struct StartPosSignals {
     pub read: ReadSignal<Option<u16>>,
     pub write: WriteSignal<Option<u16>>,
 }
 
+impl Default for StartPosSignals {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StartPosSignals {
+    pub fn new() -> Self {
+        let (read, write) = create_signal(None);
+        Self { read, write }
+    }
+
+    pub fn show(&self, address: u16) {
+        self.write.set(Some(address));
+    }
+}
+
 #[component]
-pub fn Disassembler(rows: usize) -> impl IntoView {
-    let (start_pos_read, start_pos_write) = create_signal(None);
-    provide_context(StartPosSignals {
-        read: start_pos_read.clone(),
-        write: start_pos_write.clone(),
-    });
+pub fn Disassembler<C: UiCpu>(rows: usize) -> impl IntoView {
     view! {
         <table class=style::table style:width="100%">
             <thead>
-                <FollowPCSwitch />
+                <FollowPCSwitch<C> />
                 <DisasmThead />
             </thead>
-            <DisasmTbody rows />
+            <DisasmTbody<C> rows />
         </table>
     }
 }
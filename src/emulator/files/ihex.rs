@@ -0,0 +1,86 @@
+use super::{Image, Segment};
+
+const BYTES_PER_RECORD: usize = 0x10;
+
+fn parse_record(line: &str) -> Result<Vec<u8>, String> {
+    let hex = line
+        .strip_prefix(':')
+        .ok_or("record does not start with ':'")?;
+    if hex.len() % 2 != 0 {
+        return Err("odd number of hex digits".to_string());
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| "invalid hex digit".to_string())?;
+    if bytes.len() < 5 || bytes.len() != bytes[0] as usize + 5 {
+        return Err("record length does not match its byte count".to_string());
+    }
+    let sum = bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    if sum != 0 {
+        return Err(format!(
+            "checksum mismatch (expected {:02X})",
+            bytes[bytes.len() - 1].wrapping_sub(sum)
+        ));
+    }
+    Ok(bytes)
+}
+
+/// Parses an Intel HEX file. Addresses must fit in the 64 KiB address space.
+pub fn parse(text: &str) -> Result<Image, String> {
+    let mut image = Image::default();
+    let mut base: u32 = 0;
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |msg: String| format!("Intel HEX line {}: {}", number + 1, msg);
+        let bytes = parse_record(line).map_err(error)?;
+        let address = u16::from_be_bytes([bytes[1], bytes[2]]) as u32;
+        let data = &bytes[4..bytes.len() - 1];
+        match bytes[3] {
+            0x00 => {
+                let start = base + address;
+                if start + data.len() as u32 > 0x10000 {
+                    return Err(error(format!("data at 0x{:X} is outside of memory", start)));
+                }
+                image.push(start as u16, data);
+            }
+            0x01 => return Ok(image),
+            0x02 if data.len() == 2 => base = (u16::from_be_bytes([data[0], data[1]]) as u32) << 4,
+            0x03 if data.len() == 4 => {
+                let segment = u16::from_be_bytes([data[0], data[1]]) as u32;
+                let offset = u16::from_be_bytes([data[2], data[3]]) as u32;
+                image.entry = Some(((segment << 4) + offset) as u16);
+            }
+            0x04 if data.len() == 2 => base = (u16::from_be_bytes([data[0], data[1]]) as u32) << 16,
+            0x05 if data.len() == 4 => {
+                image.entry = Some(u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as u16)
+            }
+            kind => return Err(error(format!("unsupported record type {:02X}", kind))),
+        }
+    }
+    Err("Intel HEX file has no end-of-file record".to_string())
+}
+
+fn record(kind: u8, address: u16, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend_from_slice(&address.to_be_bytes());
+    bytes.push(kind);
+    bytes.extend_from_slice(data);
+    let sum = bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    bytes.push(sum.wrapping_neg());
+    let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!(":{}\n", hex)
+}
+
+pub fn export(segment: &Segment) -> String {
+    let mut text = String::new();
+    for (address, chunk) in segment.records(BYTES_PER_RECORD) {
+        text.push_str(&record(0x00, address, chunk));
+    }
+    text.push_str(&record(0x01, 0, &[]));
+    text
+}
@@ -0,0 +1,231 @@
+use emu_lib::memory::MemoryDevice;
+use leptos::logging::{log, warn};
+use leptos::prelude::*;
+use web_sys::wasm_bindgen::closure::Closure;
+use web_sys::wasm_bindgen::JsCast;
+use web_sys::{js_sys, Blob, BlobPropertyBag, File, HtmlAnchorElement, HtmlInputElement, Url};
+
+pub mod ihex;
+pub mod srec;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ImageFormat {
+    Binary,
+    IntelHex,
+    SRecord,
+}
+
+impl ImageFormat {
+    pub fn detect(data: &[u8]) -> Self {
+        let text = data.trim_ascii_start();
+        match text {
+            [b':', ..] if data.is_ascii() => ImageFormat::IntelHex,
+            [b'S', b'0'..=b'9', ..] if data.is_ascii() => ImageFormat::SRecord,
+            _ => ImageFormat::Binary,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImageFormat::Binary => "binary",
+            ImageFormat::IntelHex => "Intel HEX",
+            ImageFormat::SRecord => "S-record",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Binary => "bin",
+            ImageFormat::IntelHex => "hex",
+            ImageFormat::SRecord => "s19",
+        }
+    }
+}
+
+/// A contiguous block of bytes placed at `address`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub address: u16,
+    pub data: Vec<u8>,
+}
+
+impl Segment {
+    /// Splits the data into records of at most `max` bytes, along with their
+    /// address. Records never cross 0xFFFF, a segment that wraps around the
+    /// address space continues at 0x0000.
+    pub fn records(&self, max: usize) -> Vec<(u16, &[u8])> {
+        let mut records = Vec::new();
+        let mut address = self.address;
+        let mut rest = self.data.as_slice();
+        while !rest.is_empty() {
+            let length = max.min(rest.len()).min(0x10000 - address as usize);
+            let (record, tail) = rest.split_at(length);
+            records.push((address, record));
+            address = address.wrapping_add(length as u16);
+            rest = tail;
+        }
+        records
+    }
+}
+
+/// The contents of a program file, decoded into memory segments.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Image {
+    pub segments: Vec<Segment>,
+    pub entry: Option<u16>,
+}
+
+impl Image {
+    /// Appends `data` at `address`, merging it into the previous segment when contiguous.
+    fn push(&mut self, address: u16, data: &[u8]) {
+        if let Some(last) = self.segments.last_mut() {
+            if last.address as usize + last.data.len() == address as usize {
+                last.data.extend_from_slice(data);
+                return;
+            }
+        }
+        self.segments.push(Segment {
+            address,
+            data: data.to_vec(),
+        });
+    }
+
+    /// Decodes `data`, raw binaries are placed at address 0.
+    pub fn parse(data: &[u8]) -> Result<(ImageFormat, Image), String> {
+        let format = ImageFormat::detect(data);
+        let text = || String::from_utf8_lossy(data);
+        let image = match format {
+            ImageFormat::Binary => Image::binary(data, 0)?,
+            ImageFormat::IntelHex => ihex::parse(&text())?,
+            ImageFormat::SRecord => srec::parse(&text())?,
+        };
+        Ok((format, image))
+    }
+
+    pub fn binary(data: &[u8], address: u16) -> Result<Image, String> {
+        if address as usize + data.len() > 0x10000 {
+            return Err(format!(
+                "{} bytes at 0x{:04X} do not fit in memory",
+                data.len(),
+                address
+            ));
+        }
+        Ok(Image {
+            segments: vec![Segment {
+                address,
+                data: data.to_vec(),
+            }],
+            entry: None,
+        })
+    }
+
+    pub fn size(&self) -> usize {
+        self.segments.iter().map(|segment| segment.data.len()).sum()
+    }
+
+    /// Writes every segment, `force` also writes through read-only devices.
+    /// Writes all of the image or nothing: when a byte cannot be written, the
+    /// bytes already written get their previous value back.
+    pub fn write_to(&self, memory: &mut impl MemoryDevice, force: bool) -> Result<(), String> {
+        let mut written = Vec::new();
+        for segment in &self.segments {
+            for (offset, value) in segment.data.iter().enumerate() {
+                let address = segment.address.wrapping_add(offset as u16);
+                let previous = memory.read_8(address).unwrap_or_default();
+                if let Err(err) = write_8(memory, address, *value, force) {
+                    // Reversed, so bytes written twice end up with their original value
+                    for (address, previous) in written.into_iter().rev() {
+                        let _ = write_8(memory, address, previous, force);
+                    }
+                    return Err(format!("Failed to write 0x{:04X}: {}", address, err));
+                }
+                written.push((address, previous));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_8(
+    memory: &mut impl MemoryDevice,
+    address: u16,
+    value: u8,
+    force: bool,
+) -> Result<(), &'static str> {
+    match force {
+        true => memory.write_8_force(address, value),
+        false => memory.write_8(address, value),
+    }
+}
+
+/// Reads `length` bytes starting at `address`, wrapping around the address space.
+pub fn read_segment(memory: &impl MemoryDevice, address: u16, length: usize) -> Segment {
+    Segment {
+        address,
+        data: (0..length)
+            .map(|offset| {
+                memory
+                    .read_8(address.wrapping_add(offset as u16))
+                    .unwrap_or_default()
+            })
+            .collect(),
+    }
+}
+
+/// Reads the first file selected in a file input and hands its bytes to `on_load`.
+pub fn read_input_file(
+    element: &HtmlInputElement,
+    on_load: impl FnOnce(String, Vec<u8>) + 'static,
+) {
+    if let Some(file) = element.files().and_then(|files| files.get(0)) {
+        read_file(file, on_load);
+    }
+    // Allow selecting the same file again
+    element.set_value("");
+}
+
+pub fn read_file(file: File, on_load: impl FnOnce(String, Vec<u8>) + 'static) {
+    log!("Loading file: {:?}", file.name());
+    let reader = web_sys::FileReader::new().unwrap();
+    let reader_clone = reader.clone();
+    let name = file.name();
+    let mut on_load = Some(on_load);
+    let onloadend_callback = Closure::wrap(Box::new(move || {
+        let array_buffer = reader_clone.result().unwrap();
+        let uint8_array = js_sys::Uint8Array::new(&array_buffer);
+        let file_content = uint8_array.to_vec();
+        log!("Loaded file with {} bytes", file_content.len());
+        if let Some(on_load) = on_load.take() {
+            on_load(name.clone(), file_content);
+        }
+    }) as Box<dyn FnMut()>);
+    reader.set_onloadend(Some(onloadend_callback.as_ref().unchecked_ref()));
+    reader.read_as_array_buffer(&file).unwrap();
+    onloadend_callback.forget();
+}
+
+/// Offers `data` to the user as a file download.
+pub fn download_bytes(name: &str, data: &[u8]) {
+    let array = js_sys::Uint8Array::from(data);
+    let parts = js_sys::Array::of1(&array);
+    let options = BlobPropertyBag::new();
+    options.set_type("application/octet-stream");
+    let result = Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+        .and_then(|blob| Url::create_object_url_with_blob(&blob))
+        .and_then(|url| {
+            let anchor = document()
+                .create_element("a")?
+                .dyn_into::<HtmlAnchorElement>()?;
+            anchor.set_href(&url);
+            anchor.set_download(name);
+            anchor.click();
+            Url::revoke_object_url(&url)
+        });
+    if let Err(err) = result {
+        warn!("Failed to download {}: {:?}", name, err);
+    }
+}
+
+pub fn download_text(name: &str, text: &str) {
+    download_bytes(name, text.as_bytes());
+}
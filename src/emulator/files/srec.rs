@@ -0,0 +1,81 @@
+use super::{Image, Segment};
+
+const BYTES_PER_RECORD: usize = 0x10;
+
+/// Parses a Motorola S-record file. Addresses must fit in the 64 KiB address space.
+pub fn parse(text: &str) -> Result<Image, String> {
+    let mut image = Image::default();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |msg: String| format!("S-record line {}: {}", number + 1, msg);
+        let (kind, hex) = line
+            .strip_prefix('S')
+            .and_then(|rest| rest.split_at_checked(1))
+            .ok_or_else(|| error("record does not start with 'S'".to_string()))?;
+        if hex.len() % 2 != 0 {
+            return Err(error("odd number of hex digits".to_string()));
+        }
+        let bytes = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| error("invalid hex digit".to_string()))?;
+        if bytes.len() < 3 || bytes.len() != bytes[0] as usize + 1 {
+            return Err(error("record length does not match its byte count".to_string()));
+        }
+        let (checksum, body) = bytes.split_last().unwrap();
+        let sum = body.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        if !sum != *checksum {
+            return Err(error(format!("checksum mismatch (expected {:02X})", !sum)));
+        }
+        let address_len = match kind {
+            "0" | "1" | "5" | "9" => 2,
+            "2" | "6" | "8" => 3,
+            "3" | "7" => 4,
+            _ => return Err(error(format!("unsupported record type S{}", kind))),
+        };
+        if body.len() < 1 + address_len {
+            return Err(error("record is too short".to_string()));
+        }
+        let address = body[1..1 + address_len]
+            .iter()
+            .fold(0u32, |address, b| (address << 8) | *b as u32);
+        let data = &body[1 + address_len..];
+        match kind {
+            "1" | "2" | "3" => {
+                if address + data.len() as u32 > 0x10000 {
+                    return Err(error(format!("data at 0x{:X} is outside of memory", address)));
+                }
+                image.push(address as u16, data);
+            }
+            "7" | "8" | "9" => {
+                image.entry = Some(address as u16);
+                return Ok(image);
+            }
+            _ => {}
+        }
+    }
+    Ok(image)
+}
+
+fn record(kind: char, address: u16, data: &[u8]) -> String {
+    let mut bytes = vec![(data.len() + 3) as u8];
+    bytes.extend_from_slice(&address.to_be_bytes());
+    bytes.extend_from_slice(data);
+    let sum = bytes.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+    bytes.push(!sum);
+    let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+    format!("S{}{}\n", kind, hex)
+}
+
+pub fn export(segment: &Segment) -> String {
+    let mut text = record('0', 0, b"emu_lib_ui");
+    for (address, chunk) in segment.records(BYTES_PER_RECORD) {
+        text.push_str(&record('1', address, chunk));
+    }
+    text.push_str(&record('9', segment.address, &[]));
+    text
+}
@@ -0,0 +1,255 @@
+use super::control::runner::RunnerSignals;
+use super::cpu::UiCpu;
+use super::disasm::StartPosSignals;
+use super::memory::AddressReadSignals;
+use super::style;
+use emu_lib::memory::MemoryDevice;
+use leptos::html::Canvas;
+use leptos::logging::warn;
+use leptos::prelude::*;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use web_sys::wasm_bindgen::{Clamped, JsCast};
+use web_sys::{CanvasRenderingContext2d, ImageData};
+
+const SIZE: usize = 0x10000;
+/// The heatmap is a square of SIDE x SIDE addresses.
+const SIDE: usize = 0x100;
+/// How often the heatmap is redrawn, and its counters decayed while running.
+const REFRESH_MS: u64 = 200;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Access {
+    Read,
+    Write,
+    Execute,
+}
+
+/// Per-address access counters, shared between the counted devices and the runner.
+/// Like watchpoints, accesses only count while armed.
+pub struct HeatShared {
+    armed: AtomicBool,
+    counters: [Vec<AtomicU32>; 3],
+}
+
+impl Default for HeatShared {
+    fn default() -> Self {
+        Self {
+            armed: AtomicBool::new(false),
+            counters: std::array::from_fn(|_| (0..SIZE).map(|_| AtomicU32::new(0)).collect()),
+        }
+    }
+}
+
+impl HeatShared {
+    pub fn count(&self, address: u16, access: Access) {
+        if !self.armed.load(Ordering::Relaxed) {
+            return;
+        }
+        self.counters[access as usize][address as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts the bytes of an instruction as executed.
+    pub fn executed(&self, pc: u16, length: u16) {
+        for offset in 0..length {
+            self.counters[Access::Execute as usize][pc.wrapping_add(offset) as usize]
+                .fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn arm(&self) {
+        self.armed.store(true, Ordering::Relaxed);
+    }
+
+    pub fn disarm(&self) {
+        self.armed.store(false, Ordering::Relaxed);
+    }
+
+    /// Drops a quarter of every counter, so old accesses fade out.
+    pub fn decay(&self) {
+        for counter in self.counters.iter().flatten() {
+            let value = counter.load(Ordering::Relaxed);
+            if value != 0 {
+                counter.store(value - value.div_ceil(4), Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn clear(&self) {
+        for counter in self.counters.iter().flatten() {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    pub fn get(&self, address: u16, access: Access) -> u32 {
+        self.counters[access as usize][address as usize].load(Ordering::Relaxed)
+    }
+}
+
+/// Wraps a memory device and counts the reads and writes made through it.
+/// Must be the outermost wrapper, the others read memory on every write.
+pub struct HeatDevice {
+    base: u16,
+    inner: Box<dyn MemoryDevice>,
+    shared: Arc<HeatShared>,
+}
+
+impl HeatDevice {
+    pub fn new(base: u16, inner: Box<dyn MemoryDevice>, shared: Arc<HeatShared>) -> Self {
+        Self {
+            base,
+            inner,
+            shared,
+        }
+    }
+}
+
+impl MemoryDevice for HeatDevice {
+    fn size(&self) -> usize {
+        self.inner.size()
+    }
+
+    fn read_8(&self, addr: u16) -> Result<u8, &'static str> {
+        self.shared
+            .count(self.base.wrapping_add(addr), Access::Read);
+        self.inner.read_8(addr)
+    }
+
+    fn write_8(&mut self, addr: u16, data: u8) -> Result<(), &'static str> {
+        self.shared
+            .count(self.base.wrapping_add(addr), Access::Write);
+        self.inner.write_8(addr, data)
+    }
+
+    fn write_8_force(&mut self, addr: u16, data: u8) -> Result<(), &'static str> {
+        self.shared
+            .count(self.base.wrapping_add(addr), Access::Write);
+        self.inner.write_8_force(addr, data)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct HeatSignals {
+    pub shared: StoredValue<Arc<HeatShared>>,
+}
+
+impl HeatSignals {
+    pub fn new(shared: Arc<HeatShared>) -> Self {
+        Self {
+            shared: StoredValue::new(shared),
+        }
+    }
+}
+
+/// Brightness for a counter, logarithmic so rare accesses still show up.
+fn intensity(count: u32) -> u8 {
+    ((count as f32 + 1.0).log2() * 32.0).min(255.0) as u8
+}
+
+fn draw(shared: &HeatShared, context: &CanvasRenderingContext2d) {
+    let mut pixels = vec![0u8; SIZE * 4];
+    for (address, pixel) in pixels.chunks_exact_mut(4).enumerate() {
+        let address = address as u16;
+        pixel[0] = intensity(shared.get(address, Access::Write));
+        pixel[1] = intensity(shared.get(address, Access::Read));
+        pixel[2] = intensity(shared.get(address, Access::Execute));
+        pixel[3] = 0xFF;
+    }
+    let image = ImageData::new_with_u8_clamped_array(Clamped(&pixels), SIDE as u32);
+    if let Err(err) = image.and_then(|image| context.put_image_data(&image, 0.0, 0.0)) {
+        warn!("Failed to draw the heatmap: {:?}", err);
+    }
+}
+
+#[component]
+pub fn Heatmap<C: UiCpu>() -> impl IntoView {
+    let heat = expect_context::<HeatSignals>();
+    let runner = expect_context::<RunnerSignals<C>>();
+    let address_signals = expect_context::<AddressReadSignals>();
+    let start_pos_signals = expect_context::<StartPosSignals>();
+    let canvas_ref = NodeRef::<Canvas>::new();
+    let hovered = RwSignal::new(None::<u16>);
+
+    let refresh = move || {
+        let shared = heat.shared.get_value();
+        if runner.active.get_untracked() {
+            shared.decay();
+        }
+        let Some(canvas) = canvas_ref.get_untracked() else {
+            return;
+        };
+        let context = canvas
+            .get_context("2d")
+            .ok()
+            .flatten()
+            .and_then(|context| context.dyn_into::<CanvasRenderingContext2d>().ok());
+        if let Some(context) = context {
+            draw(&shared, &context);
+        }
+    };
+    match set_interval_with_handle(refresh, Duration::from_millis(REFRESH_MS)) {
+        Ok(handle) => on_cleanup(move || handle.clear()),
+        Err(err) => warn!("Failed to start the heatmap: {:?}", err),
+    }
+
+    // Address under the mouse, the canvas is scaled to the width of the panel
+    let address_at = move |event: &web_sys::MouseEvent| {
+        let canvas = canvas_ref.get_untracked()?;
+        let scale = SIDE as f64 / canvas.client_width().max(1) as f64;
+        let x = ((event.offset_x() as f64 * scale) as usize).min(SIDE - 1);
+        let y = ((event.offset_y() as f64 * scale) as usize).min(SIDE - 1);
+        Some((y * SIDE + x) as u16)
+    };
+    let status = move || match hovered.get() {
+        Some(address) => {
+            let shared = heat.shared.get_value();
+            format!(
+                "{:04X}  R {}  W {}  X {}",
+                address,
+                shared.get(address, Access::Read),
+                shared.get(address, Access::Write),
+                shared.get(address, Access::Execute)
+            )
+        }
+        None => "Red: writes, green: reads (including opcode fetches), blue: executed".to_string(),
+    };
+
+    view! {
+        <table style:width="100%" class=style::table>
+            <tr>
+                <td colspan=2 style:line-height="0">
+                    <canvas
+                        node_ref=canvas_ref
+                        width=SIDE
+                        height=SIDE
+                        style:width="100%"
+                        style:image-rendering="pixelated"
+                        style:cursor="crosshair"
+                        title="Click to show the address in the memory editor and disassembler"
+                        on:mousemove=move |event| hovered.set(address_at(&event))
+                        on:mouseleave=move |_| hovered.set(None)
+                        on:click=move |event| {
+                            if let Some(address) = address_at(&event) {
+                                address_signals.show(address);
+                                start_pos_signals.show(address);
+                            }
+                        }
+                    />
+                </td>
+            </tr>
+            <tr>
+                <th class=style::tableleft style:padding="0.3rem" style:width="100%">
+                    <span>{status}</span>
+                </th>
+                <th
+                    class=style::tablebutton
+                    style:padding="0.3rem"
+                    on:click=move |_| heat.shared.get_value().clear()
+                >
+                    "Clear"
+                </th>
+            </tr>
+        </table>
+    }
+}
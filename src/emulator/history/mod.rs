@@ -0,0 +1,312 @@
+use super::cpu::{RegisterState, UiCpu};
+use super::{style, EmuSignals};
+use emu_lib::cpu::Cpu;
+use emu_lib::emulator::Emulator;
+use emu_lib::memory::MemoryDevice;
+use leptos::logging::warn;
+use leptos::prelude::*;
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use web_sys::HtmlInputElement;
+
+pub const DEFAULT_BUDGET: usize = 100_000;
+
+/// Absolute addresses and previous values of every byte written since the
+/// last time the log was drained.
+pub type WriteLog = Arc<Mutex<Vec<(u16, u8)>>>;
+
+/// Wraps a memory device and logs the previous value of every byte written to it.
+pub struct LoggedDevice {
+    base: u16,
+    inner: Box<dyn MemoryDevice>,
+    log: WriteLog,
+}
+
+impl LoggedDevice {
+    pub fn new(base: u16, inner: Box<dyn MemoryDevice>, log: WriteLog) -> Self {
+        Self { base, inner, log }
+    }
+
+    fn log_write(&self, addr: u16, old: Result<u8, &'static str>) {
+        if let (Ok(old), Ok(mut log)) = (old, self.log.lock()) {
+            log.push((self.base.wrapping_add(addr), old));
+        }
+    }
+}
+
+impl MemoryDevice for LoggedDevice {
+    fn size(&self) -> usize {
+        self.inner.size()
+    }
+
+    fn read_8(&self, addr: u16) -> Result<u8, &'static str> {
+        self.inner.read_8(addr)
+    }
+
+    fn write_8(&mut self, addr: u16, data: u8) -> Result<(), &'static str> {
+        let old = self.inner.read_8(addr);
+        self.inner.write_8(addr, data)?;
+        self.log_write(addr, old);
+        Ok(())
+    }
+
+    fn write_8_force(&mut self, addr: u16, data: u8) -> Result<(), &'static str> {
+        let old = self.inner.read_8(addr);
+        self.inner.write_8_force(addr, data)?;
+        self.log_write(addr, old);
+        Ok(())
+    }
+}
+
+/// State needed to undo one instruction.
+struct JournalEntry<R> {
+    registers: R,
+    halted: bool,
+    writes: Vec<(u16, u8)>,
+}
+
+pub struct Journal<R: RegisterState> {
+    log: WriteLog,
+    undo: VecDeque<JournalEntry<R>>,
+    redo: Vec<JournalEntry<R>>,
+    budget: usize,
+    last: Option<(R, bool)>,
+}
+
+impl<R: RegisterState> Journal<R> {
+    pub fn new(log: WriteLog, budget: usize) -> Self {
+        Self {
+            log,
+            undo: VecDeque::new(),
+            redo: Vec::new(),
+            budget,
+            last: None,
+        }
+    }
+
+    fn take_writes(&self) -> Vec<(u16, u8)> {
+        self.log
+            .lock()
+            .map(|mut log| std::mem::take(&mut *log))
+            .unwrap_or_default()
+    }
+
+    /// Must be called before executing, so writes made outside of execution
+    /// (memory editor, file loads) are not attributed to the next instruction.
+    pub fn begin<C: UiCpu<Registers = R>>(&mut self, emu: &Emulator<C>) {
+        self.take_writes();
+        self.last = Some((emu.cpu.capture_registers(), emu.cpu.halted()));
+    }
+
+    /// Records the instruction that was just executed, adding the addresses
+    /// it wrote to `written`.
+    pub fn record<C: UiCpu<Registers = R>>(
+        &mut self,
+        emu: &Emulator<C>,
+        written: &mut HashSet<u16>,
+    ) {
+        let writes = self.take_writes();
+        written.extend(writes.iter().map(|(address, _)| *address));
+        let current = (emu.cpu.capture_registers(), emu.cpu.halted());
+        let Some((registers, halted)) = self.last.replace(current) else {
+            return;
+        };
+        if self.budget == 0 {
+            return;
+        }
+        self.redo.clear();
+        self.undo.push_back(JournalEntry {
+            registers,
+            halted,
+            writes,
+        });
+        while self.undo.len() > self.budget {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Applies `entry` and returns the entry that reverts it.
+    fn apply<C: UiCpu<Registers = R>>(
+        &self,
+        entry: JournalEntry<R>,
+        emu: &mut Emulator<C>,
+    ) -> JournalEntry<R> {
+        let inverse = JournalEntry {
+            registers: emu.cpu.capture_registers(),
+            halted: emu.cpu.halted(),
+            writes: entry
+                .writes
+                .iter()
+                .rev()
+                .map(|&(addr, old)| {
+                    let current = emu.memory.read_8(addr).unwrap_or_default();
+                    if let Err(err) = emu.memory.write_8_force(addr, old) {
+                        warn!("Failed to restore 0x{:04X}: {}", addr, err);
+                    }
+                    (addr, current)
+                })
+                .collect(),
+        };
+        emu.cpu.restore_registers(&entry.registers);
+        emu.cpu.set_halted(entry.halted);
+        self.take_writes();
+        inverse
+    }
+
+    pub fn step_back<C: UiCpu<Registers = R>>(&mut self, emu: &mut Emulator<C>) -> bool {
+        let Some(entry) = self.undo.pop_back() else {
+            return false;
+        };
+        let inverse = self.apply(entry, emu);
+        self.redo.push(inverse);
+        true
+    }
+
+    pub fn step_forward<C: UiCpu<Registers = R>>(&mut self, emu: &mut Emulator<C>) -> bool {
+        let Some(entry) = self.redo.pop() else {
+            return false;
+        };
+        let inverse = self.apply(entry, emu);
+        self.undo.push_back(inverse);
+        true
+    }
+
+    pub fn position(&self) -> usize {
+        self.undo.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.undo.len() + self.redo.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        while self.undo.len() > self.budget {
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.last = None;
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct HistorySignals<R: RegisterState> {
+    pub journal: StoredValue<Journal<R>>,
+    pub position: RwSignal<usize>,
+    pub length: RwSignal<usize>,
+    pub budget: RwSignal<usize>,
+}
+
+impl<R: RegisterState> HistorySignals<R> {
+    pub fn new(log: WriteLog) -> Self {
+        Self {
+            journal: StoredValue::new(Journal::new(log, DEFAULT_BUDGET)),
+            position: RwSignal::new(0),
+            length: RwSignal::new(0),
+            budget: RwSignal::new(DEFAULT_BUDGET),
+        }
+    }
+
+    /// Publishes the journal size to the UI, the journal itself is not reactive.
+    pub fn sync(&self) {
+        let (position, length) = self
+            .journal
+            .with_value(|journal| (journal.position(), journal.len()));
+        self.position.set(position);
+        self.length.set(length);
+    }
+
+    pub fn seek<C: UiCpu<Registers = R>>(&self, emu_signals: EmuSignals<C>, position: usize) {
+        emu_signals.write.update(|emu| {
+            self.journal.update_value(|journal| {
+                while journal.position() > position && journal.step_back(emu) {}
+                while journal.position() < position && journal.step_forward(emu) {}
+            });
+        });
+        self.sync();
+    }
+
+    pub fn step_back<C: UiCpu<Registers = R>>(&self, emu_signals: EmuSignals<C>) {
+        let position = self.position.get_untracked();
+        if position > 0 {
+            self.seek(emu_signals, position - 1);
+        }
+    }
+
+    pub fn set_budget(&self, budget: usize) {
+        self.journal
+            .update_value(|journal| journal.set_budget(budget));
+        self.budget.set(budget);
+        self.sync();
+    }
+
+    pub fn clear(&self) {
+        self.journal.update_value(|journal| journal.clear());
+        self.sync();
+    }
+}
+
+#[component]
+pub fn Timeline<C: UiCpu>() -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let history = expect_context::<HistorySignals<C::Registers>>();
+    let status = move || format!("{}/{}", history.position.get(), history.length.get());
+    view! {
+        <table style:width="100%" class=style::table>
+            <tr>
+                <th class=style::tabletop style:padding="0.3rem">
+                    <span>"History"</span>
+                </th>
+                <th class=style::tablecell style:width="100%">
+                    <input
+                        type="range"
+                        min="0"
+                        style:width="100%"
+                        prop:max=move || history.length.get().to_string()
+                        prop:value=move || history.position.get().to_string()
+                        on:input=move |event| {
+                            if let Ok(position) = event_target_value(&event).parse::<usize>() {
+                                history.seek(emu_signals, position);
+                            }
+                        }
+                    />
+                </th>
+                <th class=style::tabletop style:padding="0.3rem">
+                    <span>{status}</span>
+                </th>
+                <th class=style::tablecell>
+                    <input
+                        style:width="8ch"
+                        title="History budget (instructions)"
+                        prop:value=move || history.budget.get().to_string()
+                        on:change=move |event| {
+                            match event_target_value(&event).parse::<usize>() {
+                                Ok(budget) => history.set_budget(budget),
+                                Err(_) => {
+                                    warn!("Invalid history budget");
+                                    event_target::<HtmlInputElement>(&event)
+                                        .set_value(&history.budget.get_untracked().to_string());
+                                }
+                            }
+                        }
+                    />
+                </th>
+                <th
+                    class=style::tablebutton
+                    style:padding="0.3rem"
+                    on:click=move |_| history.clear()
+                >
+                    "Clear"
+                </th>
+            </tr>
+        </table>
+    }
+}
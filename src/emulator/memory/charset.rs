@@ -0,0 +1,48 @@
+/// Character set used to show memory as text.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Charset {
+    #[default]
+    Ascii,
+    ZxSpectrum,
+    CpM,
+}
+
+/// ZX Spectrum block graphics 0x80-0x8F, bits are top right, top left,
+/// bottom right and bottom left.
+const ZX_BLOCKS: [char; 16] = [
+    ' ', '▝', '▘', '▀', '▗', '▐', '▚', '▜', '▖', '▞', '▌', '▛', '▄', '▟', '▙', '█',
+];
+
+impl Charset {
+    pub const ALL: [Charset; 3] = [Charset::Ascii, Charset::ZxSpectrum, Charset::CpM];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Charset::Ascii => "ASCII",
+            Charset::ZxSpectrum => "ZX Spectrum",
+            Charset::CpM => "CP/M",
+        }
+    }
+
+    /// The printable character for `byte`, if it has one.
+    pub fn decode(&self, byte: u8) -> Option<char> {
+        let ascii = |byte: u8| (0x20..0x7F).contains(&byte).then_some(byte as char);
+        match self {
+            Charset::Ascii => ascii(byte),
+            Charset::ZxSpectrum => match byte {
+                0x5E => Some('↑'),
+                0x60 => Some('£'),
+                0x7F => Some('©'),
+                0x80..=0x8F => Some(ZX_BLOCKS[byte as usize - 0x80]),
+                _ => ascii(byte),
+            },
+            // Bit 7 is commonly used as a flag, e.g. to mark the last character of a string
+            Charset::CpM => ascii(byte & 0x7F),
+        }
+    }
+
+    /// The lowest byte that decodes to `char`.
+    pub fn encode(&self, char: char) -> Option<u8> {
+        (0..=u8::MAX).find(|byte| self.decode(*byte) == Some(char))
+    }
+}
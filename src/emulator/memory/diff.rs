@@ -0,0 +1,175 @@
+use super::AddressReadSignals;
+use crate::emulator::cpu::UiCpu;
+use crate::emulator::stats::StatsSignals;
+use crate::emulator::{style, EmuSignals};
+use emu_lib::memory::MemoryDevice;
+use leptos::prelude::*;
+
+/// Most bytes shown for each side of a changed range.
+const PREVIEW_BYTES: usize = 8;
+/// Most ranges listed.
+const MAX_RANGES: usize = 256;
+
+/// A run of consecutive changed addresses.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct DiffRange {
+    pub start: u16,
+    pub old: Vec<u8>,
+    pub new: Vec<u8>,
+}
+
+impl DiffRange {
+    pub fn end(&self) -> u16 {
+        self.start + (self.old.len() - 1) as u16
+    }
+}
+
+fn preview(bytes: &[u8]) -> String {
+    let mut text = bytes
+        .iter()
+        .take(PREVIEW_BYTES)
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if bytes.len() > PREVIEW_BYTES {
+        text.push_str(" …");
+    }
+    text
+}
+
+pub fn diff(old: &[u8], new: &[u8]) -> Vec<DiffRange> {
+    let mut ranges: Vec<DiffRange> = Vec::new();
+    for (address, (before, after)) in old.iter().zip(new).enumerate() {
+        if before == after {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(range) if range.end() as usize + 1 == address => {
+                range.old.push(*before);
+                range.new.push(*after);
+            }
+            _ => ranges.push(DiffRange {
+                start: address as u16,
+                old: vec![*before],
+                new: vec![*after],
+            }),
+        }
+    }
+    ranges
+}
+
+/// The baseline captured to compare against, with the instruction count it was taken at.
+struct Baseline {
+    memory: Vec<u8>,
+    instructions: u64,
+}
+
+#[component]
+pub fn MemoryDiff<C: UiCpu>() -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let address_signals = expect_context::<AddressReadSignals>();
+    let stats = expect_context::<StatsSignals>();
+    let baseline = StoredValue::new(None::<Baseline>);
+    let ranges = RwSignal::new(None::<Vec<DiffRange>>);
+    let status = RwSignal::new("No baseline".to_string());
+
+    let read_memory = move || {
+        emu_signals.read.with_untracked(|emu| {
+            (0..emu.memory.size())
+                .map(|index| emu.memory.read_8(index as u16).unwrap_or_default())
+                .collect::<Vec<u8>>()
+        })
+    };
+    let capture = move |_| {
+        let instructions = stats.instructions.get_untracked();
+        baseline.set_value(Some(Baseline {
+            memory: read_memory(),
+            instructions,
+        }));
+        ranges.set(None);
+        status.set(format!("Baseline at instruction {}", instructions));
+    };
+    let compare = move |_| {
+        let result = baseline.with_value(|baseline| {
+            baseline.as_ref().map(|baseline| {
+                let ranges = diff(&baseline.memory, &read_memory());
+                let bytes: usize = ranges.iter().map(|range| range.old.len()).sum();
+                let mut message = format!(
+                    "{} bytes in {} ranges changed over {} instructions",
+                    bytes,
+                    ranges.len(),
+                    stats
+                        .instructions
+                        .get_untracked()
+                        .saturating_sub(baseline.instructions)
+                );
+                if ranges.len() > MAX_RANGES {
+                    message.push_str(&format!(", showing the first {}", MAX_RANGES));
+                }
+                (ranges, message)
+            })
+        });
+        match result {
+            Some((result, message)) => {
+                ranges.set(Some(result));
+                status.set(message);
+            }
+            None => status.set("Capture a baseline first".to_string()),
+        }
+    };
+
+    view! {
+        <table style:width="100%" class=style::table>
+            <tr>
+                <th class=style::tabletop style:padding="0.3rem">
+                    <span>"Diff"</span>
+                </th>
+                <th class=style::tablebutton style:padding="0.3rem" on:click=capture>
+                    "Capture"
+                </th>
+                <th class=style::tablebutton style:padding="0.3rem" on:click=compare>
+                    "Compare"
+                </th>
+                <th class=style::tableleft style:padding="0.3rem" style:width="100%" colspan=2>
+                    <span>{status}</span>
+                </th>
+            </tr>
+            <Show when=move || ranges.with(|ranges| ranges.as_ref().is_some_and(|r| !r.is_empty()))>
+                <tr>
+                    <th class=style::tabletop>"Start"</th>
+                    <th class=style::tabletop>"End"</th>
+                    <th class=style::tabletop>"Size"</th>
+                    <th class=style::tabletop>"Old"</th>
+                    <th class=style::tabletop>"New"</th>
+                </tr>
+                <For
+                    each=move || ranges.get().unwrap_or_default().into_iter().take(MAX_RANGES)
+                    key=|range| range.clone()
+                    let:range
+                >
+                    <tr
+                        style:cursor="pointer"
+                        title="Show in the memory editor"
+                        on:click=move |_| address_signals.show(range.start)
+                    >
+                        <td class=style::tableleft style:padding="0 0.3rem">
+                            {format!("{:04X}", range.start)}
+                        </td>
+                        <td class=style::tableleft style:padding="0 0.3rem">
+                            {format!("{:04X}", range.end())}
+                        </td>
+                        <td class=style::tableleft style:padding="0 0.3rem">
+                            {range.old.len()}
+                        </td>
+                        <td class=style::tableleft style:padding="0 0.3rem">
+                            {preview(&range.old)}
+                        </td>
+                        <td class=style::tableleft style:padding="0 0.3rem">
+                            {preview(&range.new)}
+                        </td>
+                    </tr>
+                </For>
+            </Show>
+        </table>
+    }
+}
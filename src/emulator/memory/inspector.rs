@@ -0,0 +1,262 @@
+use super::charset::Charset;
+use super::AddressReadSignals;
+use crate::emulator::cpu::UiCpu;
+use crate::emulator::registers::Base;
+use crate::emulator::{style, EmuSignals};
+use emu_lib::cpu::Cpu;
+use emu_lib::memory::MemoryDevice;
+use leptos::logging::warn;
+use leptos::prelude::*;
+use web_sys::HtmlInputElement;
+
+/// A way of reading the bytes at the cursor.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Interpretation {
+    U8,
+    I8,
+    U16Le,
+    I16Le,
+    U16Be,
+    I16Be,
+    Bcd,
+    Binary,
+    Char,
+}
+
+impl Interpretation {
+    const ALL: [Interpretation; 9] = [
+        Interpretation::U8,
+        Interpretation::I8,
+        Interpretation::U16Le,
+        Interpretation::I16Le,
+        Interpretation::U16Be,
+        Interpretation::I16Be,
+        Interpretation::Bcd,
+        Interpretation::Binary,
+        Interpretation::Char,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Interpretation::U8 => "u8",
+            Interpretation::I8 => "i8",
+            Interpretation::U16Le => "u16 LE",
+            Interpretation::I16Le => "i16 LE",
+            Interpretation::U16Be => "u16 BE",
+            Interpretation::I16Be => "i16 BE",
+            Interpretation::Bcd => "BCD",
+            Interpretation::Binary => "Binary",
+            Interpretation::Char => "Char",
+        }
+    }
+
+    fn length(&self) -> usize {
+        match self {
+            Interpretation::U16Le
+            | Interpretation::I16Le
+            | Interpretation::U16Be
+            | Interpretation::I16Be => 2,
+            _ => 1,
+        }
+    }
+
+    /// `bytes` holds exactly `length` bytes.
+    fn decode(&self, bytes: &[u8], charset: Charset) -> String {
+        let byte = bytes[0] as u16;
+        let word = || match self {
+            Interpretation::U16Be | Interpretation::I16Be => {
+                u16::from_be_bytes([bytes[0], bytes[1]])
+            }
+            _ => u16::from_le_bytes([bytes[0], bytes[1]]),
+        };
+        match self {
+            Interpretation::U8 => Base::Unsigned.format(byte, 8),
+            Interpretation::I8 => Base::Signed.format(byte, 8),
+            Interpretation::U16Le | Interpretation::U16Be => Base::Unsigned.format(word(), 16),
+            Interpretation::I16Le | Interpretation::I16Be => Base::Signed.format(word(), 16),
+            Interpretation::Bcd => match (byte >> 4, byte & 0xF) {
+                (high, low) if high < 10 && low < 10 => format!("{}{}", high, low),
+                _ => "Invalid".to_string(),
+            },
+            Interpretation::Binary => Base::Binary.format(byte, 8),
+            Interpretation::Char => match charset.decode(bytes[0]) {
+                Some(char) => format!("'{}'", char),
+                None => "-".to_string(),
+            },
+        }
+    }
+
+    fn encode(&self, text: &str, charset: Charset) -> Option<Vec<u8>> {
+        let byte = |base: Base| base.parse(text, 8).map(|value| vec![value as u8]);
+        match self {
+            Interpretation::U8 => byte(Base::Unsigned),
+            Interpretation::I8 => byte(Base::Signed),
+            Interpretation::Binary => byte(Base::Binary),
+            Interpretation::U16Le => Some(Base::Unsigned.parse(text, 16)?.to_le_bytes().to_vec()),
+            Interpretation::I16Le => Some(Base::Signed.parse(text, 16)?.to_le_bytes().to_vec()),
+            Interpretation::U16Be => Some(Base::Unsigned.parse(text, 16)?.to_be_bytes().to_vec()),
+            Interpretation::I16Be => Some(Base::Signed.parse(text, 16)?.to_be_bytes().to_vec()),
+            Interpretation::Bcd => {
+                let value = text
+                    .trim()
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|value| *value < 100)?;
+                Some(vec![((value / 10) << 4) | (value % 10)])
+            }
+            Interpretation::Char => {
+                let text = text.trim().trim_matches('\'');
+                let mut chars = text.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(char), None) => Some(vec![charset.encode(char)?]),
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+/// Color a byte produces on the `CanvasDisplay`, which reads it as RGB332.
+fn rgb332(byte: u8) -> String {
+    let red = byte & 0b11100000;
+    let green = (byte & 0b00011100) << 3;
+    let blue = (byte & 0b00000011) << 6;
+    format!("#{:02x}{:02x}{:02x}", red, green, blue)
+}
+
+fn from_rgb(color: &str) -> Option<u8> {
+    let color = color.strip_prefix('#')?;
+    let channel = |index: usize| u8::from_str_radix(color.get(index..index + 2)?, 16).ok();
+    let (red, green, blue) = (channel(0)?, channel(2)?, channel(4)?);
+    Some((red & 0b11100000) | ((green >> 3) & 0b00011100) | (blue >> 6))
+}
+
+#[component]
+pub fn DataInspector<C: UiCpu>() -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let address_signals = expect_context::<AddressReadSignals>();
+    let charset = expect_context::<RwSignal<Charset>>();
+
+    // The bytes from the cursor on, up to the longest interpretation
+    let bytes = move || {
+        let address = address_signals.cursor.get();
+        emu_signals.read.with(|emu| {
+            (0..2)
+                .map(|offset| address as usize + offset)
+                .take_while(|index| *index < emu.memory.size())
+                .map_while(|index| emu.memory.read_8(index as u16).ok())
+                .collect::<Vec<u8>>()
+        })
+    };
+    let write = move |data: &[u8]| -> Result<(), String> {
+        let address = address_signals.cursor.get_untracked();
+        let mut result = Ok(());
+        emu_signals.write.update(|emu| {
+            if address as usize + data.len() > emu.memory.size() {
+                result = Err("Not enough memory after the cursor".to_string());
+                return;
+            }
+            for (offset, value) in data.iter().enumerate() {
+                let target = address + offset as u16;
+                if let Err(err) = emu.memory.write_8(target, *value) {
+                    result = Err(format!("Failed to write 0x{:04X}: {}", target, err));
+                    return;
+                }
+            }
+        });
+        result
+    };
+    let instruction = move || {
+        let address = address_signals.cursor.get();
+        emu_signals.read.with(
+            |emu| match emu.cpu.parser().ins_from_mem(&emu.memory, address) {
+                Ok(ins) => ins.to_string(),
+                Err(_) => "-".to_string(),
+            },
+        )
+    };
+    let color = move || bytes().first().map(|byte| rgb332(*byte));
+
+    let rows = Interpretation::ALL
+        .iter()
+        .map(|interpretation| {
+            let interpretation = *interpretation;
+            let text = move || {
+                let bytes = bytes();
+                match bytes.len() >= interpretation.length() {
+                    true => interpretation.decode(&bytes[..interpretation.length()], charset.get()),
+                    false => "-".to_string(),
+                }
+            };
+            view! {
+                <tr>
+                    <th class=style::tabletop style:padding="0 0.3rem" style:white-space="nowrap">
+                        <span>{interpretation.name()}</span>
+                    </th>
+                    <th class=style::tablecell style:width="100%">
+                        <input
+                            style:width="100%"
+                            prop:value=text
+                            on:change=move |event| {
+                                let value = event_target_value(&event);
+                                let result = interpretation
+                                    .encode(&value, charset.get_untracked())
+                                    .ok_or_else(|| format!("Invalid {} value", interpretation.name()))
+                                    .and_then(|data| write(&data));
+                                if let Err(err) = result {
+                                    warn!("{}: {}", err, value);
+                                    event_target::<HtmlInputElement>(&event).set_value(&text());
+                                }
+                            }
+                        />
+                    </th>
+                </tr>
+            }
+        })
+        .collect_view();
+
+    view! {
+        <table style:width="100%" class=style::table>
+            <tr>
+                <th class=style::tabletop colspan=2>
+                    <span>{move || format!("Inspector 0x{:04X}", address_signals.cursor.get())}</span>
+                </th>
+            </tr>
+            {rows}
+            <tr>
+                <th class=style::tabletop style:padding="0 0.3rem">
+                    <span>"Instruction"</span>
+                </th>
+                <th
+                    class=style::tableleft
+                    style:padding="0 0.3rem"
+                    title="Read only, edit the bytes to change it"
+                >
+                    <span>{instruction}</span>
+                </th>
+            </tr>
+            <tr>
+                <th class=style::tabletop style:padding="0 0.3rem">
+                    <span>"RGB332"</span>
+                </th>
+                <th class=style::tablecell style:text-align="left">
+                    <input
+                        type="color"
+                        title="Color on the display, picking one writes the nearest RGB332 byte"
+                        prop:value=move || color().unwrap_or_default()
+                        on:change=move |event| {
+                            let value = event_target_value(&event);
+                            let result = from_rgb(&value)
+                                .ok_or_else(|| format!("Invalid color {}", value))
+                                .and_then(|byte| write(&[byte]));
+                            if let Err(err) = result {
+                                warn!("{}", err);
+                            }
+                        }
+                    />
+                    <span style:padding="0 0.3rem">{move || color().unwrap_or_default()}</span>
+                </th>
+            </tr>
+        </table>
+    }
+}
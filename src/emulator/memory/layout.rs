@@ -0,0 +1,489 @@
+use super::map::DeviceKind;
+use crate::emulator::display::CanvasDisplay;
+use crate::emulator::{files, style};
+use emu_lib::memory::MemoryDevice;
+use leptos::logging::warn;
+use leptos::prelude::*;
+use std::sync::{Arc, Mutex};
+use web_sys::HtmlInputElement;
+
+/// Resolution of the display device, one byte per pixel.
+pub const DISPLAY_RES: (u16, u16) = (256, 192);
+pub const DISPLAY_SIZE: usize = DISPLAY_RES.0 as usize * DISPLAY_RES.1 as usize;
+const ADDRESS_SPACE: usize = 0x10000;
+
+/// RAM or ROM whose bytes can be shared with the mirrors of it.
+pub struct Block {
+    data: Arc<Mutex<Vec<u8>>>,
+    size: usize,
+    read_only: bool,
+}
+
+impl Block {
+    /// `size` may be larger than `data`, addresses wrap around it.
+    pub fn new(data: Arc<Mutex<Vec<u8>>>, size: usize, read_only: bool) -> Self {
+        Self {
+            data,
+            size,
+            read_only,
+        }
+    }
+}
+
+impl MemoryDevice for Block {
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn read_8(&self, addr: u16) -> Result<u8, &'static str> {
+        let data = self.data.lock().or(Err("Failed to lock memory"))?;
+        data.get(addr as usize % data.len().max(1))
+            .copied()
+            .ok_or("Address out of bounds")
+    }
+
+    fn write_8(&mut self, addr: u16, data: u8) -> Result<(), &'static str> {
+        if self.read_only {
+            return Err("Write to read-only memory");
+        }
+        self.write_8_force(addr, data)
+    }
+
+    fn write_8_force(&mut self, addr: u16, data: u8) -> Result<(), &'static str> {
+        let mut buffer = self.data.lock().or(Err("Failed to lock memory"))?;
+        let len = buffer.len().max(1);
+        match buffer.get_mut(addr as usize % len) {
+            Some(value) => {
+                *value = data;
+                Ok(())
+            }
+            None => Err("Address out of bounds"),
+        }
+    }
+}
+
+/// One device of the machine layout.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceSpec {
+    pub name: String,
+    pub kind: DeviceKind,
+    pub start: u16,
+    pub size: usize,
+    /// Start of the device a mirror repeats.
+    pub source: u16,
+    /// Initial contents of a ROM.
+    pub data: Vec<u8>,
+}
+
+impl DeviceSpec {
+    pub fn new(name: &str, kind: DeviceKind, start: u16, size: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            start,
+            size,
+            source: 0,
+            data: Vec::new(),
+        }
+    }
+
+    pub fn end(&self) -> usize {
+        self.start as usize + self.size - 1
+    }
+}
+
+/// The devices making up the address space, `Memory` places them one after
+/// another so they must cover it in order without gaps or overlaps.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MachineLayout {
+    pub devices: Vec<DeviceSpec>,
+}
+
+impl Default for MachineLayout {
+    fn default() -> Self {
+        Self {
+            devices: vec![
+                DeviceSpec::new("RAM", DeviceKind::Ram, 0, 0x1000),
+                DeviceSpec::new("Display", DeviceKind::Display, 0x1000, DISPLAY_SIZE),
+                DeviceSpec::new(
+                    "RAM",
+                    DeviceKind::Ram,
+                    (0x1000 + DISPLAY_SIZE) as u16,
+                    ADDRESS_SPACE - DISPLAY_SIZE - 0x1000,
+                ),
+            ],
+        }
+    }
+}
+
+impl MachineLayout {
+    pub fn sort(&mut self) {
+        self.devices.sort_by_key(|device| device.start);
+    }
+
+    pub fn has_display(&self) -> bool {
+        self.devices
+            .iter()
+            .any(|device| device.kind == DeviceKind::Display)
+    }
+
+    /// Everything wrong with the layout, empty when it can be built.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut devices: Vec<&DeviceSpec> = self.devices.iter().collect();
+        devices.sort_by_key(|device| device.start);
+        let mut next = 0usize;
+        for device in &devices {
+            if device.size == 0 {
+                errors.push(format!(
+                    "{} at 0x{:04X} is empty",
+                    device.name, device.start
+                ));
+                continue;
+            }
+            let start = device.start as usize;
+            if start > next {
+                errors.push(format!("Nothing at 0x{:04X}-0x{:04X}", next, start - 1));
+            } else if start < next {
+                errors.push(format!(
+                    "{} at 0x{:04X} overlaps the previous device",
+                    device.name, start
+                ));
+            }
+            if device.end() >= ADDRESS_SPACE {
+                errors.push(format!("{} ends past 0xFFFF", device.name));
+            }
+            next = next.max(start + device.size);
+            match device.kind {
+                DeviceKind::Display if device.size != DISPLAY_SIZE => {
+                    errors.push(format!("The display must be 0x{:04X} bytes", DISPLAY_SIZE))
+                }
+                DeviceKind::Rom if device.data.len() > device.size => errors.push(format!(
+                    "{} is smaller than its {} byte image",
+                    device.name,
+                    device.data.len()
+                )),
+                DeviceKind::Mirror => {
+                    let source = devices.iter().find(|source| source.start == device.source);
+                    match source.map(|source| source.kind) {
+                        None => errors.push(format!(
+                            "{} mirrors 0x{:04X}, where no device starts",
+                            device.name, device.source
+                        )),
+                        Some(DeviceKind::Mirror) => {
+                            errors.push(format!("{} mirrors another mirror", device.name))
+                        }
+                        Some(_) => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        if next < ADDRESS_SPACE {
+            errors.push(format!("Nothing at 0x{:04X}-0xFFFF", next));
+        }
+        let displays = devices
+            .iter()
+            .filter(|device| device.kind == DeviceKind::Display)
+            .count();
+        if displays > 1 {
+            errors.push("Only one display is supported".to_string());
+        }
+        errors
+    }
+
+    /// Creates the devices in address order, ready for `Memory::add_device`.
+    /// The layout must be valid.
+    pub fn build(
+        &self,
+        display: CanvasDisplay,
+    ) -> Vec<(String, DeviceKind, Box<dyn MemoryDevice>)> {
+        let mut layout = self.clone();
+        layout.sort();
+        let storage = |device: &DeviceSpec| match device.kind {
+            DeviceKind::Display => display.buffer.clone(),
+            _ => {
+                let mut data = device.data.clone();
+                data.resize(device.size, 0);
+                Arc::new(Mutex::new(data))
+            }
+        };
+        let buffers: Vec<(u16, Arc<Mutex<Vec<u8>>>)> = layout
+            .devices
+            .iter()
+            .filter(|device| device.kind != DeviceKind::Mirror)
+            .map(|device| (device.start, storage(device)))
+            .collect();
+        let buffer = |start: u16| {
+            buffers
+                .iter()
+                .find(|(source, _)| *source == start)
+                .map(|(_, buffer)| buffer.clone())
+                .unwrap_or_default()
+        };
+        let mut display = Some(display);
+        layout
+            .devices
+            .iter()
+            .map(|device| {
+                let built: Box<dyn MemoryDevice> = match device.kind {
+                    DeviceKind::Display => match display.take() {
+                        Some(display) => Box::new(display),
+                        None => Box::new(Block::new(buffer(device.start), device.size, false)),
+                    },
+                    DeviceKind::Mirror => {
+                        let read_only = layout.devices.iter().any(|source| {
+                            source.start == device.source && source.kind == DeviceKind::Rom
+                        });
+                        Box::new(Block::new(buffer(device.source), device.size, read_only))
+                    }
+                    kind => Box::new(Block::new(
+                        buffer(device.start),
+                        device.size,
+                        kind == DeviceKind::Rom,
+                    )),
+                };
+                (device.name.clone(), device.kind, built)
+            })
+            .collect()
+    }
+}
+
+fn parse_hex(text: &str) -> Option<usize> {
+    usize::from_str_radix(text.trim().trim_start_matches("0x"), 16).ok()
+}
+
+#[component]
+fn LayoutTr(index: usize, draft: RwSignal<MachineLayout>) -> impl IntoView {
+    let field = move |get: fn(&DeviceSpec) -> String| {
+        move || draft.with(|layout| layout.devices.get(index).map(get).unwrap_or_default())
+    };
+    let update = move |set: &dyn Fn(&mut DeviceSpec)| {
+        draft.update(|layout| {
+            if let Some(device) = layout.devices.get_mut(index) {
+                set(device);
+            }
+        })
+    };
+    let kind = move || draft.with(|layout| layout.devices.get(index).map(|device| device.kind));
+    // Input for a hex field of the device, values above `max` are rejected
+    let hex_input = move |max: usize,
+                          title: &'static str,
+                          get: fn(&DeviceSpec) -> usize,
+                          set: fn(&mut DeviceSpec, usize)| {
+        let text = move || {
+            draft.with(|layout| {
+                layout
+                    .devices
+                    .get(index)
+                    .map(|device| format!("{:04X}", get(device)))
+                    .unwrap_or_default()
+            })
+        };
+        view! {
+            <input
+                style:width="6ch"
+                maxlength=format!("{:X}", max).len()
+                title=title
+                prop:value=text
+                on:change=move |event| match parse_hex(&event_target_value(&event)) {
+                    Some(value) if value <= max => update(&|device| set(device, value)),
+                    _ => {
+                        warn!("Invalid hex value");
+                        event_target::<HtmlInputElement>(&event).set_value(&text());
+                    }
+                }
+            />
+        }
+    };
+    let load_rom = move |event| {
+        let element = event_target::<HtmlInputElement>(&event);
+        files::read_input_file(&element, move |_, data| {
+            update(&|device| device.data = data.clone());
+        });
+    };
+
+    view! {
+        <tr>
+            <th class=style::tablecell>
+                <input
+                    style:width="100%"
+                    title="Name"
+                    prop:value=field(|device| device.name.clone())
+                    on:change=move |event| {
+                        let name = event_target_value(&event);
+                        update(&|device| device.name = name.clone());
+                    }
+                />
+            </th>
+            <th class=style::tablecell>
+                <select
+                    prop:value=move || kind().map(|kind| kind.name()).unwrap_or_default()
+                    on:change=move |event| {
+                        let value = event_target_value(&event);
+                        if let Some(kind) = DeviceKind::ALL.iter().find(|kind| kind.name() == value) {
+                            update(&|device| {
+                                device.kind = *kind;
+                                if device.kind == DeviceKind::Display {
+                                    device.size = DISPLAY_SIZE;
+                                }
+                            });
+                        }
+                    }
+                >
+                    {DeviceKind::ALL
+                        .iter()
+                        .map(|kind| view! { <option value=kind.name()>{kind.name()}</option> })
+                        .collect_view()}
+                </select>
+            </th>
+            <th class=style::tablecell>
+                {hex_input(
+                    u16::MAX as usize,
+                    "Start",
+                    |device| device.start as usize,
+                    |device, value| device.start = value as u16,
+                )}
+            </th>
+            <th class=style::tablecell>
+                {hex_input(
+                    ADDRESS_SPACE,
+                    "Size",
+                    |device| device.size,
+                    |device, value| device.size = value,
+                )}
+            </th>
+            <th class=style::tablecell>
+                {move || match kind() {
+                    Some(DeviceKind::Mirror) => {
+                        hex_input(
+                                u16::MAX as usize,
+                                "Start of the mirrored device",
+                                |device| device.source as usize,
+                                |device, value| device.source = value as u16,
+                            )
+                            .into_any()
+                    }
+                    Some(DeviceKind::Rom) => {
+                        view! {
+                            <input
+                                type="file"
+                                style:width="12ch"
+                                title=field(|device| format!("Image: {} bytes", device.data.len()))
+                                on:change=load_rom
+                            />
+                        }
+                            .into_any()
+                    }
+                    _ => ().into_any(),
+                }}
+            </th>
+            <th
+                class=style::tablebutton
+                style:padding="0 0.3rem"
+                on:click=move |_| {
+                    draft
+                        .update(|layout| {
+                            if index < layout.devices.len() {
+                                layout.devices.remove(index);
+                            }
+                        })
+                }
+            >
+                "Remove"
+            </th>
+        </tr>
+    }
+}
+
+/// Edits a copy of the layout, applying it rebuilds the machine.
+#[component]
+pub fn LayoutEditor(layout: RwSignal<MachineLayout>) -> impl IntoView {
+    let draft = RwSignal::new(layout.get_untracked());
+    let errors = Memo::new(move |_| draft.with(|draft| draft.validate()));
+    let add = move |_| {
+        draft.update(|draft| {
+            let start = draft
+                .devices
+                .iter()
+                .map(|device| device.start as usize + device.size)
+                .max()
+                .unwrap_or(0)
+                .min(0xFFFF);
+            draft.devices.push(DeviceSpec::new(
+                "RAM",
+                DeviceKind::Ram,
+                start as u16,
+                ADDRESS_SPACE - start,
+            ));
+        })
+    };
+    let apply = move |_| {
+        if !errors.with_untracked(Vec::is_empty) {
+            return;
+        }
+        draft.update(MachineLayout::sort);
+        layout.set(draft.get_untracked());
+    };
+    let apply_class = move || match errors.with(Vec::is_empty) {
+        true => style::tablebutton,
+        false => style::tablebuttoninvert,
+    };
+
+    view! {
+        <table style:width="100%" class=style::table>
+            <tr>
+                <th class=style::tabletop>"Name"</th>
+                <th class=style::tabletop>"Kind"</th>
+                <th class=style::tabletop>"Start"</th>
+                <th class=style::tabletop>"Size"</th>
+                <th class=style::tabletop>"Source"</th>
+                <th class=style::tabletop></th>
+            </tr>
+            <For
+                each=move || 0..draft.with(|draft| draft.devices.len())
+                key=|index| *index
+                let:index
+            >
+                <LayoutTr index draft />
+            </For>
+            <tr>
+                <th class=style::tablebutton style:padding="0.3rem" on:click=add>
+                    "Add"
+                </th>
+                <th
+                    class=style::tablebutton
+                    style:padding="0.3rem"
+                    on:click=move |_| draft.set(layout.get_untracked())
+                >
+                    "Revert"
+                </th>
+                <th
+                    class=apply_class
+                    style:padding="0.3rem"
+                    colspan=2
+                    title="Rebuilds the machine, resetting it"
+                    on:click=apply
+                >
+                    "Apply"
+                </th>
+                <th class=style::tablebutton style:padding="0.3rem" colspan=2 on:click=move |_| draft.set(MachineLayout::default())>
+                    "Default"
+                </th>
+            </tr>
+            <Show when=move || errors.with(|errors| !errors.is_empty())>
+                <tr>
+                    <td colspan=6 class=style::tableleft style:padding="0 0.3rem">
+                        {move || {
+                            errors
+                                .get()
+                                .into_iter()
+                                .map(|error| view! { <div>{error}</div> })
+                                .collect_view()
+                        }}
+                    </td>
+                </tr>
+            </Show>
+        </table>
+    }
+}
@@ -0,0 +1,157 @@
+use super::AddressReadSignals;
+use crate::emulator::style;
+use leptos::prelude::*;
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DeviceKind {
+    Ram,
+    Rom,
+    /// Repeats another device.
+    Mirror,
+    Display,
+}
+
+impl DeviceKind {
+    pub const ALL: [DeviceKind; 4] = [
+        DeviceKind::Ram,
+        DeviceKind::Rom,
+        DeviceKind::Mirror,
+        DeviceKind::Display,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeviceKind::Ram => "RAM",
+            DeviceKind::Rom => "ROM",
+            DeviceKind::Mirror => "Mirror",
+            DeviceKind::Display => "Display",
+        }
+    }
+
+    /// Color the device is shown with in the map and the memory editor.
+    pub fn color(&self) -> &'static str {
+        match self {
+            DeviceKind::Ram => "#3c9d5d",
+            DeviceKind::Rom => "#d98c2b",
+            DeviceKind::Mirror => "#4f8fd1",
+            DeviceKind::Display => "#8a5cc2",
+        }
+    }
+}
+
+/// Where a device registered in `Memory` lives in the address space.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DeviceRegion {
+    pub name: String,
+    pub kind: DeviceKind,
+    pub start: u16,
+    pub size: usize,
+}
+
+impl DeviceRegion {
+    pub fn range(&self) -> Range<usize> {
+        self.start as usize..self.start as usize + self.size
+    }
+
+    pub fn end(&self) -> usize {
+        self.start as usize + self.size - 1
+    }
+}
+
+/// Layout of the devices in `Memory`, in the order they were added.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryMap {
+    pub regions: Vec<DeviceRegion>,
+}
+
+impl MemoryMap {
+    /// Registers a device right after the previous one, the same way `Memory::add_device` does.
+    pub fn push(&mut self, name: &str, kind: DeviceKind, size: usize) -> u16 {
+        let start = self
+            .regions
+            .last()
+            .map(|region| region.start as usize + region.size)
+            .unwrap_or(0);
+        self.regions.push(DeviceRegion {
+            name: name.to_string(),
+            kind,
+            start: start as u16,
+            size,
+        });
+        start as u16
+    }
+
+    pub fn region_at(&self, address: u16) -> Option<&DeviceRegion> {
+        self.regions
+            .iter()
+            .find(|region| region.range().contains(&(address as usize)))
+    }
+
+    /// Devices overlapping `range`.
+    pub fn touched(&self, range: Range<usize>) -> Vec<&DeviceRegion> {
+        self.regions
+            .iter()
+            .filter(|region| region.range().start < range.end && range.start < region.range().end)
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct MemoryMapSignals {
+    pub read: ReadSignal<MemoryMap>,
+    pub write: WriteSignal<MemoryMap>,
+}
+
+impl MemoryMapSignals {
+    pub fn new(map: MemoryMap) -> Self {
+        let (read, write) = create_signal(map);
+        Self { read, write }
+    }
+}
+
+#[component]
+pub fn MemoryMapPanel() -> impl IntoView {
+    let map_signals = expect_context::<MemoryMapSignals>();
+    let address_signals = expect_context::<AddressReadSignals>();
+    view! {
+        <table style:width="100%" class=style::table>
+            <tr>
+                <th class=style::tabletop></th>
+                <th class=style::tabletop>"Device"</th>
+                <th class=style::tabletop>"Kind"</th>
+                <th class=style::tabletop>"Start"</th>
+                <th class=style::tabletop>"End"</th>
+                <th class=style::tabletop>"Size"</th>
+            </tr>
+            <For
+                each=move || map_signals.read.get().regions.into_iter().enumerate()
+                key=|region| region.clone()
+                let:region
+            >
+                <tr
+                    style:cursor="pointer"
+                    title="Show in the memory editor"
+                    on:click=move |_| address_signals.show(region.1.start)
+                >
+                    <td class=style::tableleft style:background-color=region.1.kind.color()></td>
+                    <td class=style::tableleft style:padding="0 0.3rem">
+                        {region.1.name.clone()}
+                    </td>
+                    <td class=style::tableleft style:padding="0 0.3rem">
+                        {region.1.kind.name()}
+                    </td>
+                    <td class=style::tableleft style:padding="0 0.3rem">
+                        {format!("{:04X}", region.1.start)}
+                    </td>
+                    <td class=style::tableleft style:padding="0 0.3rem">
+                        {format!("{:04X}", region.1.end())}
+                    </td>
+                    <td class=style::tableleft style:padding="0 0.3rem">
+                        {format!("{} bytes", region.1.size)}
+                    </td>
+                </tr>
+            </For>
+        </table>
+    }
+}
@@ -1,225 +1,428 @@
+use super::changes::ChangeSignals;
+use super::cpu::UiCpu;
 use super::{style, EmuSignals};
-use emu_lib::cpu::z80::Z80;
-use emu_lib::emulator::Emulator;
+use charset::Charset;
 use emu_lib::memory::MemoryDevice;
-use leptos::logging::{log, warn};
+use leptos::html::Div;
+use leptos::logging::warn;
 use leptos::prelude::*;
-use leptos::*;
+use map::MemoryMapSignals;
+use range::SelectionSignals;
 use std::borrow::BorrowMut;
 use web_sys::wasm_bindgen::JsCast;
-use web_sys::HtmlInputElement; // Added
+use web_sys::{HtmlInputElement, KeyboardEvent, MouseEvent};
+
+pub mod charset;
+pub mod diff;
+pub mod inspector;
+pub mod layout;
+pub mod map;
+pub mod range;
+pub mod search;
+
+const ROW_HEIGHT: f64 = 22.0;
 
 #[component]
 fn MemThead(width: usize) -> impl IntoView {
     let address_signals = expect_context::<AddressReadSignals>();
-    let suffix = move |offset: u16| {
-        let address: u16 = address_signals.read.get();
-        if offset > (0x10 - (address & 0x00FF)) {
-            let offset = (address & 0x00FF) + offset;
-            format!("{:02X}", offset)
-        } else {
-            let offset = (address & 0x00FF) + offset;
-            format!("{:01X}", offset)
-        }
-    };
+    let charset = expect_context::<RwSignal<Charset>>();
     let view = view! {
-        <thead>
-            <tr>
-                <th class=style::tableleft></th>
+        <thead style:position="sticky" style:top="0" style:z-index="1">
+            <tr style:height=format!("{}px", ROW_HEIGHT)>
+                <th class=style::tableleft style:display="flex" style:border="none">
+                    <span>"0x"</span>
+                    <input
+                        class=style::tablecount
+                        style:width="100%"
+                        maxlength=4
+                        title="Go to address"
+                        prop:value=move || format!("{:04X}", address_signals.cursor.get())
+                        on:change=move |event| {
+                            let element = event_target::<HtmlInputElement>(&event);
+                            match u16::from_str_radix(&element.value(), 16) {
+                                Ok(address) => address_signals.show(address),
+                                Err(_) => {
+                                    warn!("Invalid hex value");
+                                    element
+                                        .set_value(
+                                            &format!("{:04X}", address_signals.cursor.get_untracked()),
+                                        );
+                                }
+                            }
+                        }
+                    />
+                </th>
                 {(0..width)
                     .map(move |x| {
                         view! {
                             <th class=style::tabletop style:min-width="2.5ch">
-                                <span>{move || suffix(x as u16)}</span>
+                                <span>{format!("{:X}", x)}</span>
                             </th>
                         }
                     })
                     .collect_view()}
+                <th class=style::tabletop>
+                    <select
+                        title="Character set"
+                        prop:value=move || charset.get().name()
+                        on:change=move |event| {
+                            let value = event_target_value(&event);
+                            if let Some(set) = Charset::ALL.iter().find(|set| set.name() == value) {
+                                charset.set(*set);
+                            }
+                        }
+                    >
+                        {Charset::ALL
+                            .iter()
+                            .map(|set| view! { <option value=set.name()>{set.name()}</option> })
+                            .collect_view()}
+                    </select>
+                </th>
             </tr>
         </thead>
     };
     Some(view)
 }
 
-#[component]
-fn MemCell(index: usize) -> impl IntoView {
-    let emu_signals = expect_context::<EmuSignals>();
-    let i_getval = move || -> Result<u8, &str> {
-        if index >= emu_signals.read.with(|emu| emu.memory.size()) {
-            return Err("??");
-        }
-        emu_signals.read.with(|emu| emu.memory.read_8(index as u16))
-    };
+/// High nibble typed into the cursor cell, waiting for the low one.
+#[derive(Clone, Copy)]
+struct PendingNibble(RwSignal<Option<u8>>);
 
-    let s_getval = move || -> String {
-        match i_getval() {
-            Ok(val) => format!("{:02X}", val),
-            Err(msg) => msg.to_string(),
-        }
-    };
-
-    let i_setval = move |value: &u8| -> Result<(), &str> {
-        if index > u16::MAX as usize {
-            return Err("Index out of bounds");
-        }
-        let mut result = Err("Unknown error");
-        emu_signals.write.update(|emu: &mut Emulator<Z80>| {
-            result = emu.memory.write_8(index as u16, *value);
-        });
-        result
-    };
-
-    let s_setval = move |value: &str| -> Result<(), &str> {
-        let hexval = u8::from_str_radix(value, 16);
-        match hexval {
-            Ok(val) => {
-                i_setval(&val)?;
-                Ok(())
+#[component]
+fn MemCell<C: UiCpu>(index: usize) -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let address_signals = expect_context::<AddressReadSignals>();
+    let pending = expect_context::<PendingNibble>().0;
+    let selection = expect_context::<SelectionSignals>();
+    let text = move || {
+        if address_signals.cursor.get() as usize == index {
+            if let Some(high) = pending.get() {
+                return format!("{:X}_", high);
             }
-            Err(_) => Err("Invalid hex value"),
         }
-    };
-
-    let view = view! {
-        <input
-            maxlength=2
-            prop:value=move || s_getval()
-            style:width="100%"
-            on:change=move |ev| {
-                let elem_val = event_target_value(&ev);
-                if let Err(err) = s_setval(&elem_val) {
-                    warn!("Error saving value: {} at pos: {} with error: {}", elem_val,index,err);
-                    let real_val = s_getval();
-                    event_target::<HtmlInputElement>(&ev).borrow_mut().set_value(&real_val);
-                }
+        emu_signals.read.with(|emu| {
+            if index >= emu.memory.size() {
+                return "??".to_string();
             }
-            on:click=move |event| {
-                event
-                    .target()
-                    .map(|target| {
-                        let element = target.dyn_into::<HtmlInputElement>().unwrap();
-                        element.select();
-                    });
+            match emu.memory.read_8(index as u16) {
+                Ok(val) => format!("{:02X}", val),
+                Err(_) => "??".to_string(),
             }
-        />
+        })
     };
-    Some(view)
+    view! {
+        <span
+            style:display="block"
+            style:cursor="default"
+            on:mousedown=move |_| {
+                pending.set(None);
+                address_signals.cursor.set(index as u16);
+                selection.begin(index as u16);
+            }
+            on:mouseenter=move |event: MouseEvent| {
+                match event.buttons() & 1 {
+                    0 => selection.end(),
+                    _ => selection.extend(index as u16),
+                }
+            }
+            on:mouseup=move |_| selection.end()
+        >
+            {text}
+        </span>
+    }
 }
 
 #[component]
-fn MemThs(width: usize, row_start: usize) -> impl IntoView {
+fn MemThs<C: UiCpu>(width: usize, row_start: usize) -> impl IntoView {
+    let changes = expect_context::<ChangeSignals>();
+    let map_signals = expect_context::<MemoryMapSignals>();
+    let address_signals = expect_context::<AddressReadSignals>();
+    let selection = expect_context::<SelectionSignals>();
+    let class = move |index: usize| {
+        let cursor = match address_signals.cursor.get() as usize == index {
+            true => style::cursor,
+            false => "",
+        };
+        let selected = match selection.contains(index as u16) {
+            true => style::selected,
+            false => "",
+        };
+        format!(
+            "{} {} {} {}",
+            style::tablecell,
+            changes.memory_class(index as u16),
+            selected,
+            cursor
+        )
+    };
+    // Stripe along the bottom of the cell, so it does not hide the change highlight
+    let tint = move |index: usize| {
+        map_signals.read.with(|map| {
+            map.region_at(index as u16)
+                .map(|region| format!("inset 0 -3px 0 {}", region.kind.color()))
+                .unwrap_or_default()
+        })
+    };
     view! {
         <For each=move || { row_start..(row_start + width) } key=move |index| *index let:index>
-            <th class=style::tablecell>
-                <MemCell index />
+            <th class=move || class(index) style:box-shadow=move || tint(index)>
+                <MemCell<C> index />
             </th>
         </For>
     }
 }
 
+/// The row as text, typing into it writes the characters that changed.
+/// Bytes shown as '.' have no character and are left alone unless replaced.
 #[component]
-fn MemTrCounter(width: usize) -> impl IntoView {
-    let emu_signals = expect_context::<EmuSignals>();
-    let address_signals = expect_context::<AddressReadSignals>();
-    let memth = move || {
-        let row_start = address_signals.read.get() as usize;
-        view! { <MemThs width row_start /> }
+fn MemChars<C: UiCpu>(width: usize, row_start: usize) -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let charset = expect_context::<RwSignal<Charset>>();
+    let text = move || {
+        let charset = charset.get();
+        emu_signals.read.with(|emu| {
+            (row_start..(row_start + width).min(emu.memory.size()))
+                .map(|index| match emu.memory.read_8(index as u16) {
+                    Ok(byte) => charset.decode(byte).unwrap_or('.'),
+                    Err(_) => '?',
+                })
+                .collect::<String>()
+        })
     };
-    let view = view! {
-        <tr>
-            <th class=style::tableleft style:display="flex" style:border="none">
-                <span>"0x"</span>
-                <input
-                    class=style::tablecount
-                    style:width="100%"
-                    maxlength=4
-                    prop:value=move || format!("{:04X}", address_signals.read.get())
-                    on:change=move |event| {
-                        event
-                            .target()
-                            .map(|target| {
-                                let element = target.dyn_into::<HtmlInputElement>().unwrap();
-                                let elem_val = &element.value();
-                                let hexval = u16::from_str_radix(elem_val, 16);
-                                match hexval {
-                                    Ok(val) => {
-                                        address_signals.write.set(val);
-                                        element.set_value(&format!("{:04X}", val));
-                                    }
-                                    Err(_) => {
-                                        log!("Invalid hex value");
-                                        element
-                                            .set_value(&format!("{:04X}", address_signals.read.get()));
-                                    }
-                                }
-                            });
-                    }
-                />
-            </th>
-            {memth}
-        </tr>
+    let write = move |value: &str| -> Result<(), String> {
+        let charset = charset.get_untracked();
+        let shown: Vec<char> = text().chars().collect();
+        let bytes = value
+            .chars()
+            .take(width)
+            .enumerate()
+            .filter(|(offset, char)| shown.get(*offset) != Some(char))
+            .map(|(offset, char)| {
+                charset
+                    .encode(char)
+                    .map(|byte| (offset, byte))
+                    .ok_or_else(|| format!("'{}' is not in the {} charset", char, charset.name()))
+            })
+            .collect::<Result<Vec<(usize, u8)>, String>>()?;
+        let mut result = Ok(());
+        emu_signals.write.update(|emu| {
+            for (offset, byte) in bytes.iter() {
+                let index = row_start + offset;
+                if index > u16::MAX as usize {
+                    break;
+                }
+                if let Err(err) = emu.memory.write_8(index as u16, *byte) {
+                    result = Err(err.to_string());
+                    break;
+                }
+            }
+        });
+        result
     };
-    Some(view)
+    view! {
+        <th class=style::tablecell>
+            <input
+                maxlength=width
+                style:width=format!("{}ch", width + 1)
+                style:font-family="monospace"
+                prop:value=text
+                on:change=move |ev| {
+                    let elem_val = event_target_value(&ev);
+                    if let Err(err) = write(&elem_val) {
+                        warn!("Error saving text: {} at pos: {} with error: {}", elem_val, row_start, err);
+                        event_target::<HtmlInputElement>(&ev).borrow_mut().set_value(&text());
+                    }
+                }
+            />
+        </th>
+    }
 }
 
 #[component]
-fn MemTr(width: usize, row_start: usize) -> impl IntoView {
+fn MemTr<C: UiCpu>(width: usize, row_start: usize) -> impl IntoView {
     view! {
-        <tr>
+        <tr style:height=format!("{}px", ROW_HEIGHT)>
             <th class=style::tableleft>
                 <span>{format!("0x{:04X}", row_start)}</span>
             </th>
-            <MemThs width row_start />
+            <MemThs<C> width row_start />
+            <MemChars<C> width row_start />
         </tr>
     }
 }
 
+/// Only the rows in view are rendered, spacer rows stand in for the others.
 #[component]
-pub fn MemTbody(width: usize, rows: usize) -> impl IntoView {
-    let emu_signals = expect_context::<EmuSignals>();
+pub fn MemTbody<C: UiCpu>(width: usize, rows: usize) -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
     let address_signals = expect_context::<AddressReadSignals>();
-    let addr_start = move || address_signals.read.get() as usize + width;
-    let addr_end = move || {
-        emu_signals
-            .read
-            .with(|emu| emu.memory.size())
-            .min(addr_start() + width * rows)
+    let total_rows = move || emu_signals.read.with(|emu| emu.memory.size()).div_ceil(width);
+    let first_row = move || address_signals.read.get() as usize / width;
+    let visible = move || {
+        let first = first_row();
+        let last = total_rows().min(first + rows + 1);
+        (first..last).map(|row| row * width).collect::<Vec<_>>()
+    };
+    let spacer_top = move || format!("{}px", first_row() as f64 * ROW_HEIGHT);
+    let spacer_bottom = move || {
+        let remaining = total_rows().saturating_sub(first_row() + rows + 1);
+        format!("{}px", remaining as f64 * ROW_HEIGHT)
     };
     let view = view! {
         <tbody>
-            <MemTrCounter width />
-            <For
-                each=move || { (addr_start()..addr_end()).step_by(width) }
-                key=|row_start| *row_start
-                let:row_start
-            >
-                <MemTr width row_start />
+            <tr style:height=spacer_top></tr>
+            <For each=visible key=|row_start| *row_start let:row_start>
+                <MemTr<C> width row_start />
             </For>
+            <tr style:height=spacer_bottom></tr>
         </tbody>
     };
     Some(view)
 }
 
-#[derive(Clone)]
+/// Scroll position and cursor of the memory editor, provided by the emulator
+/// so other panels can jump it to an address.
+#[derive(Clone, Copy)]
 pub struct AddressReadSignals {
+    /// First address of the top row in view.
     pub read: ReadSignal<u16>,
     pub write: WriteSignal<u16>,
+    /// Address of the selected cell, it does not move when memory changes.
+    pub cursor: RwSignal<u16>,
+}
+
+impl Default for AddressReadSignals {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AddressReadSignals {
+    pub fn new() -> Self {
+        let (read, write) = create_signal(0);
+        Self {
+            read,
+            write,
+            cursor: RwSignal::new(0),
+        }
+    }
+
+    /// Moves the cursor to `address` and scrolls it to the top.
+    pub fn show(&self, address: u16) {
+        self.cursor.set(address);
+        self.write.set(address);
+    }
 }
 
 #[component]
-pub fn MemEditor(width: usize, rows: usize) -> impl IntoView {
-    let emu_signals = expect_context::<EmuSignals>();
+pub fn MemEditor<C: UiCpu>(width: usize, rows: usize) -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let address_signals = expect_context::<AddressReadSignals>();
+    provide_context(RwSignal::new(Charset::default()));
+    let pending = RwSignal::new(None::<u8>);
+    provide_context(PendingNibble(pending));
+    provide_context(SelectionSignals::new());
+    let container = NodeRef::<Div>::new();
 
-    let (address_read, address_write) = create_signal(0);
-    provide_context(AddressReadSignals {
-        read: address_read.clone(),
-        write: address_write.clone(),
+    // Keep the scroll position in sync with the first row in view, both ways
+    Effect::new(move |_| {
+        let top = (address_signals.read.get() as usize / width) as f64 * ROW_HEIGHT;
+        if let Some(container) = container.get() {
+            if (container.scroll_top() as f64 - top).abs() >= ROW_HEIGHT {
+                container.set_scroll_top(top as i32);
+            }
+        }
     });
+    let scrolled = move |_| {
+        if let Some(container) = container.get_untracked() {
+            let row = (container.scroll_top() as f64 / ROW_HEIGHT) as usize;
+            if row != address_signals.read.get_untracked() as usize / width {
+                address_signals.write.set((row * width) as u16);
+            }
+        }
+    };
+
+    let size = move || emu_signals.read.with_untracked(|emu| emu.memory.size());
+    let move_cursor = move |delta: isize| {
+        let last = size().saturating_sub(1) as isize;
+        let cursor = (address_signals.cursor.get_untracked() as isize + delta).clamp(0, last);
+        pending.set(None);
+        address_signals.cursor.set(cursor as u16);
+        // Scroll just enough to keep the cursor in view
+        let row = cursor as usize / width;
+        let first = address_signals.read.get_untracked() as usize / width;
+        if row < first {
+            address_signals.write.set((row * width) as u16);
+        } else if row >= first + rows {
+            address_signals.write.set(((row + 1 - rows) * width) as u16);
+        }
+    };
+    let type_digit = move |digit: u8| {
+        let Some(high) = pending.get_untracked() else {
+            pending.set(Some(digit));
+            return;
+        };
+        let address = address_signals.cursor.get_untracked();
+        let mut result = Ok(());
+        emu_signals.write.update(|emu| {
+            result = emu.memory.write_8(address, (high << 4) | digit);
+        });
+        match result {
+            Ok(()) => move_cursor(1),
+            Err(err) => {
+                warn!("Error saving value at pos: {:04X} with error: {}", address, err);
+                pending.set(None);
+            }
+        }
+    };
+    let keydown = move |event: KeyboardEvent| {
+        // Typing in the character column must not move the cursor
+        let in_input = event
+            .target()
+            .is_some_and(|target| target.dyn_into::<HtmlInputElement>().is_ok());
+        if in_input || event.ctrl_key() || event.alt_key() || event.meta_key() {
+            return;
+        }
+        let width = width as isize;
+        let page = width * rows as isize;
+        let column = address_signals.cursor.get_untracked() as isize % width;
+        match event.key().as_str() {
+            "ArrowLeft" => move_cursor(-1),
+            "ArrowRight" => move_cursor(1),
+            "ArrowUp" => move_cursor(-width),
+            "ArrowDown" => move_cursor(width),
+            "PageUp" => move_cursor(-page),
+            "PageDown" => move_cursor(page),
+            "Home" => move_cursor(-column),
+            "End" => move_cursor(width - 1 - column),
+            "Escape" => pending.set(None),
+            key => match key.chars().next().and_then(|c| c.to_digit(16)) {
+                Some(digit) if key.len() == 1 => type_digit(digit as u8),
+                _ => return,
+            },
+        }
+        event.prevent_default();
+    };
+
     let view = view! {
-        <table style:width="100%" class=style::table>
-            <MemThead width />
-            <MemTbody width rows />
-        </table>
+        <search::MemSearch<C> />
+        <range::RangeTools<C> />
+        <div
+            node_ref=container
+            tabindex=0
+            title="Arrows, PageUp/PageDown, Home/End to move, type hex digits to write"
+            style:height=format!("{}px", ROW_HEIGHT * (rows + 1) as f64)
+            style:overflow-y="auto"
+            style:outline="none"
+            on:scroll=scrolled
+            on:keydown=keydown
+        >
+            <table style:width="100%" class=style::table>
+                <MemThead width />
+                <MemTbody<C> width rows />
+            </table>
+        </div>
+        <inspector::DataInspector<C> />
     };
     Some(view)
 }
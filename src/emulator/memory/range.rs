@@ -0,0 +1,319 @@
+use crate::emulator::cpu::UiCpu;
+use crate::emulator::{files, style, EmuSignals};
+use emu_lib::emulator::Emulator;
+use emu_lib::memory::MemoryDevice;
+use leptos::logging::warn;
+use leptos::prelude::*;
+use web_sys::HtmlInputElement;
+
+/// Inclusive range of addresses selected in the memory editor.
+#[derive(Clone, Copy)]
+pub struct SelectionSignals {
+    pub range: RwSignal<Option<(u16, u16)>>,
+    /// Where the current drag started.
+    anchor: StoredValue<Option<u16>>,
+}
+
+impl Default for SelectionSignals {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SelectionSignals {
+    pub fn new() -> Self {
+        Self {
+            range: RwSignal::new(None),
+            anchor: StoredValue::new(None),
+        }
+    }
+
+    /// Starts a drag, a click without dragging clears the selection.
+    pub fn begin(&self, address: u16) {
+        self.anchor.set_value(Some(address));
+        self.range.set(None);
+    }
+
+    pub fn extend(&self, address: u16) {
+        if let Some(anchor) = self.anchor.get_value() {
+            self.range
+                .set(Some((anchor.min(address), anchor.max(address))));
+        }
+    }
+
+    pub fn end(&self) {
+        self.anchor.set_value(None);
+    }
+
+    pub fn contains(&self, address: u16) -> bool {
+        self.range
+            .with(|range| range.is_some_and(|(start, end)| (start..=end).contains(&address)))
+    }
+}
+
+fn read_range<C: UiCpu>(emu: &Emulator<C>, start: u16, end: u16) -> Vec<u8> {
+    files::read_segment(&emu.memory, start, end as usize - start as usize + 1).data
+}
+
+/// Writes all of `data` or nothing: when a byte cannot be written (ROM),
+/// the bytes already written get their previous value back.
+fn write_bytes<C: UiCpu>(emu: &mut Emulator<C>, address: u16, data: &[u8]) -> Result<(), String> {
+    if address as usize + data.len() > emu.memory.size() {
+        return Err(format!(
+            "{} bytes do not fit at 0x{:04X}",
+            data.len(),
+            address
+        ));
+    }
+    let previous = read_range(emu, address, address + (data.len().max(1) - 1) as u16);
+    for (offset, value) in data.iter().enumerate() {
+        let target = address + offset as u16;
+        if let Err(err) = emu.memory.write_8(target, *value) {
+            for (offset, value) in previous.iter().enumerate().take(offset) {
+                let _ = emu.memory.write_8(address + offset as u16, *value);
+            }
+            return Err(format!("Failed to write 0x{:04X}: {}", target, err));
+        }
+    }
+    Ok(())
+}
+
+/// Hex bytes separated by spaces or not, e.g. "DE AD" or "DEAD".
+fn parse_pattern(text: &str) -> Result<Vec<u8>, String> {
+    let digits: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return Err("The pattern needs two hex digits per byte".to_string());
+    }
+    digits
+        .chunks(2)
+        .map(|pair| {
+            let pair: String = pair.iter().collect();
+            u8::from_str_radix(&pair, 16).map_err(|_| format!("Invalid hex byte: {}", pair))
+        })
+        .collect()
+}
+
+#[component]
+fn RangeButton(
+    name: &'static str,
+    title: &'static str,
+    action: impl Fn() + 'static,
+) -> impl IntoView {
+    view! {
+        <th class=style::tablebutton style:padding="0.3rem" title=title on:click=move |_| action()>
+            {name}
+        </th>
+    }
+}
+
+/// Fill, zero, copy, move and download the selected range.
+#[component]
+pub fn RangeTools<C: UiCpu>() -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let selection = expect_context::<SelectionSignals>();
+    let pattern = RwSignal::new("00".to_string());
+    let destination = RwSignal::new(0u16);
+    let status = RwSignal::new(String::new());
+
+    // Runs `operation` on the selected range, returns whether it succeeded
+    let apply =
+        move |name: &str, operation: &dyn Fn(&mut Emulator<C>, u16, u16) -> Result<(), String>| {
+            let Some((start, end)) = selection.range.get_untracked() else {
+                status.set("Select a range first".to_string());
+                return false;
+            };
+            let mut result = Ok(());
+            emu_signals
+                .write
+                .update(|emu| result = operation(emu, start, end));
+            match result {
+                Ok(()) => {
+                    status.set(format!("{} 0x{:04X}-0x{:04X}", name, start, end));
+                    true
+                }
+                Err(err) => {
+                    warn!("{} failed: {}", name, err);
+                    status.set(err);
+                    false
+                }
+            }
+        };
+    let fill = move || match parse_pattern(&pattern.get_untracked()) {
+        Ok(bytes) => {
+            apply("Filled", &|emu, start, end| {
+                let data: Vec<u8> = bytes
+                    .iter()
+                    .copied()
+                    .cycle()
+                    .take(end as usize - start as usize + 1)
+                    .collect();
+                write_bytes(emu, start, &data)
+            });
+        }
+        Err(err) => status.set(err),
+    };
+    let zero = move || {
+        apply("Zeroed", &|emu, start, end| {
+            write_bytes(emu, start, &vec![0; end as usize - start as usize + 1])
+        });
+    };
+    // The source is read completely before writing, so overlapping ranges are safe
+    let copy = move || {
+        let target = destination.get_untracked();
+        apply("Copied", &|emu, start, end| {
+            let data = read_range(emu, start, end);
+            write_bytes(emu, target, &data)
+        });
+    };
+    let move_range = move || {
+        let target = destination.get_untracked();
+        let moved = apply("Moved", &|emu, start, end| {
+            let data = read_range(emu, start, end);
+            let target_end = target as usize + data.len();
+            // Source bytes left outside the destination, at most one run on each side
+            let runs: Vec<(u16, usize)> = [
+                (start as usize, (end as usize + 1).min(target as usize)),
+                ((start as usize).max(target_end), end as usize + 1),
+            ]
+            .into_iter()
+            .filter(|(from, to)| from < to)
+            .map(|(from, to)| (from as u16, to - from))
+            .collect();
+            let previous = files::read_segment(&emu.memory, target, data.len()).data;
+            write_bytes(emu, target, &data)?;
+            for (index, (from, length)) in runs.iter().enumerate() {
+                if let Err(err) = write_bytes(emu, *from, &vec![0; *length]) {
+                    // Undo the whole move, the runs do not overlap the destination
+                    for (from, length) in runs.iter().take(index) {
+                        let offset = (from - start) as usize;
+                        let _ = write_bytes(emu, *from, &data[offset..offset + length]);
+                    }
+                    let _ = write_bytes(emu, target, &previous);
+                    return Err(err);
+                }
+            }
+            Ok(())
+        });
+        if moved {
+            selection.range.update(|range| {
+                if let Some((start, end)) = *range {
+                    *range = Some((target, target.saturating_add(end - start)));
+                }
+            });
+        }
+    };
+    let download = move || {
+        let Some((start, end)) = selection.range.get_untracked() else {
+            status.set("Select a range first".to_string());
+            return;
+        };
+        let data = emu_signals
+            .read
+            .with_untracked(|emu| read_range(emu, start, end));
+        files::download_bytes(&format!("memory_{:04X}_{:04X}.bin", start, end), &data);
+    };
+
+    let set_start = move |event| match u16::from_str_radix(&event_target_value(&event), 16) {
+        Ok(start) => selection.range.update(|range| {
+            let length = range.map(|(from, to)| to - from).unwrap_or(0);
+            *range = Some((start, start.saturating_add(length)));
+        }),
+        Err(_) => {
+            warn!("Invalid hex value");
+            event_target::<HtmlInputElement>(&event).set_value("");
+        }
+    };
+    let set_length = move |event| match usize::from_str_radix(&event_target_value(&event), 16) {
+        Ok(length) if (1..=0x10000).contains(&length) => selection.range.update(|range| {
+            let start = range.map(|(start, _)| start).unwrap_or(0);
+            let end = (start as usize + length - 1).min(u16::MAX as usize);
+            *range = Some((start, end as u16));
+        }),
+        _ => {
+            warn!("Invalid length");
+            event_target::<HtmlInputElement>(&event).set_value("");
+        }
+    };
+
+    view! {
+        <table style:width="100%" class=style::table>
+            <tr>
+                <th class=style::tabletop style:padding="0.3rem">
+                    <span>"Range"</span>
+                </th>
+                <th class=style::tablecell>
+                    <input
+                        style:width="5ch"
+                        maxlength=4
+                        title="Start address, or drag over the memory cells"
+                        prop:value=move || {
+                            selection
+                                .range
+                                .get()
+                                .map(|(start, _)| format!("{:04X}", start))
+                                .unwrap_or_default()
+                        }
+                        on:change=set_start
+                    />
+                </th>
+                <th class=style::tablecell>
+                    <input
+                        style:width="6ch"
+                        maxlength=5
+                        title="Length"
+                        prop:value=move || {
+                            selection
+                                .range
+                                .get()
+                                .map(|(start, end)| format!("{:04X}", end as usize - start as usize + 1))
+                                .unwrap_or_default()
+                        }
+                        on:change=set_length
+                    />
+                </th>
+                <th class=style::tablecell style:width="100%">
+                    <input
+                        style:width="100%"
+                        title="Fill pattern, hex bytes"
+                        prop:value=pattern
+                        on:change=move |event| pattern.set(event_target_value(&event))
+                    />
+                </th>
+                <RangeButton name="Fill" title="Fill with the repeating pattern" action=fill />
+                <RangeButton name="Zero" title="Fill with zeros" action=zero />
+                <th class=style::tablecell>
+                    <input
+                        style:width="5ch"
+                        maxlength=4
+                        title="Destination address"
+                        prop:value=move || format!("{:04X}", destination.get())
+                        on:change=move |event| {
+                            match u16::from_str_radix(&event_target_value(&event), 16) {
+                                Ok(address) => destination.set(address),
+                                Err(_) => {
+                                    warn!("Invalid hex value");
+                                    event_target::<HtmlInputElement>(&event)
+                                        .set_value(&format!("{:04X}", destination.get_untracked()));
+                                }
+                            }
+                        }
+                    />
+                </th>
+                <RangeButton name="Copy" title="Copy to the destination" action=copy />
+                <RangeButton
+                    name="Move"
+                    title="Move to the destination, zeroing what is left behind"
+                    action=move_range
+                />
+                <RangeButton name="Save" title="Download as a binary file" action=download />
+            </tr>
+            <Show when=move || status.with(|status| !status.is_empty())>
+                <tr>
+                    <td colspan=10 class=style::tableleft style:padding="0 0.3rem">
+                        <span>{status}</span>
+                    </td>
+                </tr>
+            </Show>
+        </table>
+    }
+}
@@ -0,0 +1,224 @@
+use super::charset::Charset;
+use super::AddressReadSignals;
+use crate::emulator::cpu::UiCpu;
+use crate::emulator::{style, EmuSignals};
+use leptos::logging::warn;
+use leptos::prelude::*;
+
+/// Most hits kept, further matches are not listed.
+const MAX_HITS: usize = 1024;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SearchMode {
+    /// Hex bytes, `??` matches any byte.
+    #[default]
+    Hex,
+    /// Text in the memory editor's charset.
+    Text,
+    /// A 16-bit hex value, stored little-endian.
+    Word,
+}
+
+impl SearchMode {
+    pub const ALL: [SearchMode; 3] = [SearchMode::Hex, SearchMode::Text, SearchMode::Word];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SearchMode::Hex => "Hex",
+            SearchMode::Text => "Text",
+            SearchMode::Word => "Word",
+        }
+    }
+
+    /// Bytes to look for, `None` matches any byte.
+    pub fn pattern(&self, query: &str, charset: Charset) -> Result<Vec<Option<u8>>, String> {
+        let pattern = match self {
+            SearchMode::Hex => {
+                let digits: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+                if digits.len() % 2 != 0 {
+                    return Err("Hex patterns need two digits per byte".to_string());
+                }
+                digits
+                    .chunks(2)
+                    .map(|pair| {
+                        let pair: String = pair.iter().collect();
+                        match pair.as_str() {
+                            "??" => Ok(None),
+                            _ => u8::from_str_radix(&pair, 16)
+                                .map(Some)
+                                .map_err(|_| format!("Invalid hex byte: {}", pair)),
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+            SearchMode::Text => query
+                .chars()
+                .map(|char| {
+                    charset.encode(char).map(Some).ok_or_else(|| {
+                        format!("'{}' is not in the {} charset", char, charset.name())
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            SearchMode::Word => {
+                let digits = query.trim().trim_start_matches("0x");
+                let word = u16::from_str_radix(digits, 16)
+                    .map_err(|_| format!("Invalid hex word: {}", query))?;
+                word.to_le_bytes().map(Some).to_vec()
+            }
+        };
+        match pattern.is_empty() {
+            true => Err("Nothing to search for".to_string()),
+            false => Ok(pattern),
+        }
+    }
+}
+
+/// Start addresses of every match of `pattern` in `memory`.
+fn find(memory: &[u8], pattern: &[Option<u8>]) -> Vec<u16> {
+    memory
+        .windows(pattern.len())
+        .enumerate()
+        .filter(|(_, window)| {
+            window
+                .iter()
+                .zip(pattern)
+                .all(|(byte, expected)| expected.is_none_or(|expected| expected == *byte))
+        })
+        .map(|(address, _)| address as u16)
+        .take(MAX_HITS)
+        .collect()
+}
+
+#[component]
+pub fn MemSearch<C: UiCpu>() -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let address_signals = expect_context::<AddressReadSignals>();
+    let charset = expect_context::<RwSignal<Charset>>();
+    let mode = RwSignal::new(SearchMode::default());
+    let query = RwSignal::new(String::new());
+    let hits = RwSignal::new(Vec::<u16>::new());
+    let current = RwSignal::new(None::<usize>);
+
+    let select = move |index: usize| {
+        if let Some(address) = hits.with_untracked(|hits| hits.get(index).copied()) {
+            current.set(Some(index));
+            address_signals.show(address);
+        }
+    };
+    let search = move || {
+        let pattern = match mode
+            .get_untracked()
+            .pattern(&query.get_untracked(), charset.get_untracked())
+        {
+            Ok(pattern) => pattern,
+            Err(err) => {
+                warn!("{}", err);
+                return;
+            }
+        };
+        let memory: Vec<u8> = emu_signals.read.with_untracked(|emu| {
+            (0..emu.memory.size())
+                .map(|index| emu.memory.read_8(index as u16).unwrap_or_default())
+                .collect()
+        });
+        hits.set(find(&memory, &pattern));
+        current.set(None);
+        // Start from the first hit after the address currently shown
+        let shown = address_signals.read.get_untracked();
+        let first = hits.with_untracked(|hits| hits.iter().position(|hit| *hit >= shown));
+        select(first.unwrap_or(0));
+    };
+    let step = move |forward: bool| {
+        let count = hits.with_untracked(Vec::len);
+        if count == 0 {
+            return;
+        }
+        let index = match (current.get_untracked(), forward) {
+            (None, _) => 0,
+            (Some(index), true) => (index + 1) % count,
+            (Some(index), false) => (index + count - 1) % count,
+        };
+        select(index);
+    };
+    let status = move || match (current.get(), hits.with(Vec::len)) {
+        (_, 0) => "No hits".to_string(),
+        (Some(index), count) if count >= MAX_HITS => format!("{}/{}+", index + 1, count),
+        (Some(index), count) => format!("{}/{}", index + 1, count),
+        (None, count) => format!("{} hits", count),
+    };
+
+    view! {
+        <table style:width="100%" class=style::table>
+            <tr>
+                <th class=style::tablecell>
+                    <select
+                        prop:value=move || mode.get().name()
+                        on:change=move |event| {
+                            let value = event_target_value(&event);
+                            if let Some(new) = SearchMode::ALL.iter().find(|mode| mode.name() == value) {
+                                mode.set(*new);
+                            }
+                        }
+                    >
+                        {SearchMode::ALL
+                            .iter()
+                            .map(|mode| view! { <option value=mode.name()>{mode.name()}</option> })
+                            .collect_view()}
+                    </select>
+                </th>
+                <th class=style::tablecell style:width="100%">
+                    <input
+                        style:width="100%"
+                        placeholder=move || match mode.get() {
+                            SearchMode::Hex => "3E ?? CD",
+                            SearchMode::Text => "HELLO",
+                            SearchMode::Word => "1234",
+                        }
+                        prop:value=move || query.get()
+                        on:change=move |event| {
+                            query.set(event_target_value(&event));
+                            search();
+                        }
+                    />
+                </th>
+                <th class=style::tablebutton style:padding="0.3rem" on:click=move |_| search()>
+                    "Find"
+                </th>
+                <th class=style::tablebutton style:padding="0.3rem" on:click=move |_| step(false)>
+                    "Prev"
+                </th>
+                <th class=style::tablebutton style:padding="0.3rem" on:click=move |_| step(true)>
+                    "Next"
+                </th>
+                <th class=style::tabletop style:padding="0.3rem" style:white-space="nowrap">
+                    <span>{status}</span>
+                </th>
+            </tr>
+            <Show when=move || hits.with(|hits| !hits.is_empty())>
+                <tr>
+                    <td colspan=6 class=style::tableleft>
+                        <div style:max-height="4.5rem" style:overflow-y="auto">
+                            <For
+                                each=move || hits.get().into_iter().enumerate()
+                                key=|hit| *hit
+                                let:hit
+                            >
+                                <span
+                                    class=move || match current.get() == Some(hit.0) {
+                                        true => style::colorfocus,
+                                        false => "",
+                                    }
+                                    style:padding="0 0.3rem"
+                                    style:cursor="pointer"
+                                    on:click=move |_| select(hit.0)
+                                >
+                                    {format!("{:04X}", hit.1)}
+                                </span>
+                            </For>
+                        </div>
+                    </td>
+                </tr>
+            </Show>
+        </table>
+    }
+}
@@ -1,32 +1,56 @@
+use cpu::{CpuKind, UiCpu};
+use emu_lib::cpu::i8080::I8080;
 use emu_lib::cpu::z80::Z80;
-use emu_lib::cpu::Cpu;
 use emu_lib::emulator::Emulator;
-use emu_lib::memory::memdevices::RAM;
-use emu_lib::memory::Memory;
+use emu_lib::memory::{Memory, MemoryDevice};
+use heatmap::{HeatDevice, HeatShared, HeatSignals};
+use history::{HistorySignals, LoggedDevice, WriteLog};
+use memory::layout::{LayoutEditor, MachineLayout, DISPLAY_RES};
+use memory::map::{MemoryMap, MemoryMapSignals};
+use std::sync::Arc;
+use watch::{WatchShared, WatchSignals, WatchedDevice};
 use leptos::prelude::*;
 use stylance::import_style;
 
 pub mod display;
 
+pub mod breakpoints;
+pub mod changes;
 pub mod control;
+pub mod cpu;
 pub mod disasm;
+pub mod files;
+pub mod heatmap;
+pub mod history;
 // pub mod display;
 pub mod memory;
 pub mod registers;
+pub mod snapshot;
+pub mod stats;
+pub mod trace;
+pub mod watch;
 import_style!(
     #[allow(dead_code)]
     style,
     "table.module.scss"
 );
 
-#[derive(Clone)]
-pub struct EmuSignals {
-    pub read: ReadSignal<Emulator<Z80>>,
-    pub write: WriteSignal<Emulator<Z80>>,
+pub struct EmuSignals<C: UiCpu> {
+    pub read: ReadSignal<Emulator<C>>,
+    pub write: WriteSignal<Emulator<C>>,
 }
 
-impl EmuSignals {
-    pub fn new(emulator: Emulator<Z80>) -> Self {
+// Implemented by hand, deriving would require the cpu itself to be Copy
+impl<C: UiCpu> Clone for EmuSignals<C> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<C: UiCpu> Copy for EmuSignals<C> {}
+
+impl<C: UiCpu> EmuSignals<C> {
+    pub fn new(emulator: Emulator<C>) -> Self {
         let (read, write) = create_signal(emulator);
         Self { read, write }
     }
@@ -51,18 +75,45 @@ pub fn emu_with(
         </div>
     }
 }
-#[island]
-pub fn Emulator() -> impl IntoView {
-    let res = (256, 192);
+/// Wraps a device of the machine with the history log, watchpoints and heatmap.
+/// The history log and watchpoints read the old value on every write, so the
+/// log is innermost and the heatmap outermost, where those reads are not
+/// reported as accesses.
+fn instrument(
+    base: u16,
+    device: Box<dyn MemoryDevice>,
+    write_log: &WriteLog,
+    watch_shared: &Arc<WatchShared>,
+    heat_shared: &Arc<HeatShared>,
+) -> Box<dyn MemoryDevice> {
+    let device = LoggedDevice::new(base, device, write_log.clone());
+    let device = WatchedDevice::new(base, Box::new(device), watch_shared.clone());
+    Box::new(HeatDevice::new(base, Box::new(device), heat_shared.clone()))
+}
+
+#[component]
+fn EmulatorView<C: UiCpu>(layout: MachineLayout) -> impl IntoView {
+    let res = DISPLAY_RES;
     let (dsp, dsp_view) = display::gen_dsp(res.0 * res.1, res.0 as usize, 2.0);
+    let has_display = layout.has_display();
+    let devices = layout.build(dsp);
+    let write_log = WriteLog::default();
+    let watch_shared = Arc::new(WatchShared::default());
+    let heat_shared = Arc::new(HeatShared::default());
+    let mut memory_map = MemoryMap::default();
     let mut memory = Memory::new();
-    memory.add_device(Box::new(RAM::new(0x1000)));
-    memory.add_device(Box::new(dsp));
-    memory.add_device(Box::new(RAM::new(
-        0x10000 - res.0 as usize * res.1 as usize - 0x1000,
-    )));
+    for (name, kind, device) in devices {
+        let base = memory_map.push(&name, kind, device.size());
+        memory.add_device(instrument(
+            base,
+            device,
+            &write_log,
+            &watch_shared,
+            &heat_shared,
+        ));
+    }
     // let memory = Memory::new_full_ram();
-    let emulator: Emulator<Z80> = Emulator::new_w_mem(memory);
+    let emulator: Emulator<C> = Emulator::new_w_mem(memory);
     let rom_data = include_bytes!("../../color2.bin");
     // let test = "AAAAAAAABBBBBBBBCCCCCCCCDDDDDDDDEEEEEEEEFFFFFFFFGGGGGGGGHHHHHHHHIIIIIIII".to_string();//.repeat(5);
     // emulator.memory.load(test.as_bytes()).unwrap();
@@ -77,13 +128,88 @@ pub fn Emulator() -> impl IntoView {
     let emu_signals = EmuSignals::new(emulator);
     let dsp_update = Signal::derive(move || emu_signals.read.with(|_| ()));
     provide_context(emu_signals);
+    provide_context(MemoryMapSignals::new(memory_map));
+    provide_context(memory::AddressReadSignals::new());
+    provide_context(disasm::StartPosSignals::new());
+    let history = HistorySignals::<C::Registers>::new(write_log);
+    provide_context(history);
+    provide_context(breakpoints::BreakpointSignals::new());
+    provide_context(WatchSignals::new(watch_shared));
+    provide_context(HeatSignals::new(heat_shared));
+    provide_context(trace::TraceSignals::<C::Registers>::new());
+    provide_context(stats::StatsSignals::new());
+    provide_context(changes::ChangeSignals::new());
+    provide_context(control::runner::RunnerSignals::<C>::new());
+    view! {
+        <memory::MemEditor<C> width=0x10 rows=10 />
+        <memory::map::MemoryMapPanel />
+        <memory::diff::MemoryDiff<C> />
+        <disasm::Disassembler<C> rows=10 />
+        {C::registers_panel()}
+        <control::Control<C> />
+        <stats::StatsPanel<C> />
+        <history::Timeline<C> />
+        <breakpoints::BreakpointPanel<C> />
+        <watch::WatchPanel />
+        <heatmap::Heatmap<C> />
+        <trace::TracePanel<C> />
+        <snapshot::SnapshotPanel<C> />
+        {has_display.then(|| view! { <div>{dsp_view(dsp_update)}</div> })}
+    }
+}
+
+#[island]
+pub fn Emulator() -> impl IntoView {
+    let cpu = RwSignal::new(CpuKind::default());
+    let layout = RwSignal::new(MachineLayout::default());
+    let editing = RwSignal::new(false);
     view! {
         <div class=style::maincontainer style:width="38rem">
-            <memory::MemEditor width=0x10 rows=10 />
-            <disasm::Disassembler rows=10 />
-            <registers::z80::Registers />
-            <control::Control />
-            <div>{dsp_view(dsp_update)}</div>
+            <table style:width="100%" class=style::table>
+                <tr>
+                    <th class=style::tabletop style:padding="0.3rem">
+                        <span>"CPU"</span>
+                    </th>
+                    <th class=style::tablecell style:width="100%">
+                        <select
+                            style:width="100%"
+                            title="Switching cpu resets the machine"
+                            prop:value=move || cpu.get().name()
+                            on:change=move |event| {
+                                let value = event_target_value(&event);
+                                if let Some(kind) = CpuKind::ALL.iter().find(|kind| kind.name() == value) {
+                                    cpu.set(*kind);
+                                }
+                            }
+                        >
+                            {CpuKind::ALL
+                                .iter()
+                                .map(|kind| view! { <option value=kind.name()>{kind.name()}</option> })
+                                .collect_view()}
+                        </select>
+                    </th>
+                    <th
+                        class=move || match editing.get() {
+                            true => style::tablebuttoninvert,
+                            false => style::tablebutton,
+                        }
+                        style:padding="0.3rem"
+                        on:click=move |_| editing.update(|editing| *editing = !*editing)
+                    >
+                        "Memory layout"
+                    </th>
+                </tr>
+            </table>
+            <Show when=move || editing.get()>
+                <LayoutEditor layout />
+            </Show>
+            {move || {
+                let layout = layout.get();
+                match cpu.get() {
+                    CpuKind::Z80 => view! { <EmulatorView<Z80> layout /> }.into_any(),
+                    CpuKind::I8080 => view! { <EmulatorView<I8080> layout /> }.into_any(),
+                }
+            }}
         </div>
     }
 }
@@ -1,11 +1,263 @@
+use crate::emulator::cpu::{FlagLayout, RegisterState, UiCpu};
+use crate::emulator::registers::{half, Flags, Register};
+use crate::emulator::EmuSignals;
 use emu_lib::cpu::i8080::I8080;
-use emu_lib::emulator::Emulator;
 use leptos::prelude::*;
+use leptos::tachys::view::any_view::AnyView;
+use serde::Serialize;
+
+/// Copy of the 8080 register file, used to rewind or compare cpu state.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize)]
+pub struct RegisterFile {
+    pub psw: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub sp: u16,
+    pub pc: u16,
+}
+
+impl RegisterFile {
+    pub fn capture(cpu: &I8080) -> Self {
+        let registers = &cpu.registers;
+        Self {
+            psw: registers.gp.af,
+            bc: registers.gp.bc,
+            de: registers.gp.de,
+            hl: registers.gp.hl,
+            sp: registers.sp,
+            pc: registers.pc,
+        }
+    }
+
+    pub fn restore(&self, cpu: &mut I8080) {
+        let registers = &mut cpu.registers;
+        registers.gp.af = self.psw;
+        registers.gp.bc = self.bc;
+        registers.gp.de = self.de;
+        registers.gp.hl = self.hl;
+        registers.sp = self.sp;
+        registers.pc = self.pc;
+    }
+}
+
+impl RegisterState for RegisterFile {
+    const CPU: &'static str = "I8080";
+    const WORD_NAMES: &'static [&'static str] = &["PSW", "BC", "DE", "HL", "SP", "PC"];
+
+    fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    fn get(&self, name: &str) -> Option<u16> {
+        Some(match name {
+            "AF" | "PSW" => self.psw,
+            "BC" => self.bc,
+            "DE" => self.de,
+            "HL" => self.hl,
+            "SP" => self.sp,
+            "PC" => self.pc,
+            _ => return None,
+        })
+    }
+
+    fn columns(&self) -> Vec<(&'static str, u16, usize)> {
+        vec![
+            ("PSW", self.psw, 4),
+            ("BC", self.bc, 4),
+            ("DE", self.de, 4),
+            ("HL", self.hl, 4),
+            ("SP", self.sp, 4),
+        ]
+    }
+
+    fn to_words(&self) -> Vec<u16> {
+        vec![self.psw, self.bc, self.de, self.hl, self.sp, self.pc]
+    }
+
+    fn from_words(words: &[u16]) -> Option<Self> {
+        let [psw, bc, de, hl, sp, pc] = *words else {
+            return None;
+        };
+        Some(Self {
+            psw,
+            bc,
+            de,
+            hl,
+            sp,
+            pc,
+        })
+    }
+}
+
+impl UiCpu for I8080 {
+    type Registers = RegisterFile;
+    // Bits 1, 3 and 5 are fixed on the 8080
+    const FLAGS: FlagLayout = [
+        ("S", Some(("M", "P")), None),
+        ("Z", Some(("Z", "NZ")), None),
+        ("0", None, Some(false)),
+        ("AC", None, None),
+        ("0", None, Some(false)),
+        ("P", Some(("PE", "PO")), None),
+        ("1", None, Some(true)),
+        ("CY", Some(("C", "NC")), None),
+    ];
+    const STEP_OVER_MNEMONICS: &'static [&'static str] = &[
+        "CALL", "CNZ", "CZ", "CNC", "CC", "CPO", "CPE", "CP", "CM", "RST",
+    ];
+    const RETURN_MNEMONICS: &'static [&'static str] =
+        &["RET", "RNZ", "RZ", "RNC", "RC", "RPO", "RPE", "RP", "RM"];
+
+    fn capture_registers(&self) -> RegisterFile {
+        RegisterFile::capture(self)
+    }
+
+    fn restore_registers(&mut self, registers: &RegisterFile) {
+        registers.restore(self);
+    }
+
+    fn set_pc(&mut self, pc: u16) {
+        self.registers.pc = pc;
+    }
+
+    fn flags(&self) -> u8 {
+        self.registers.gp.af as u8
+    }
+
+    fn set_flags(&mut self, flags: u8) {
+        let flags = (flags & !0b0010_1000) | 0b0000_0010;
+        self.registers.gp.af = (self.registers.gp.af & 0xFF00) | flags as u16;
+    }
+
+    fn registers_panel() -> AnyView {
+        view! { <registers /> }.into_any()
+    }
+}
 
 #[component]
-pub fn registers(
-    _emu_read: ReadSignal<Emulator<I8080>>,
-    _emu_write: WriteSignal<Emulator<I8080>>,
-) -> impl IntoView {
-    view! { <div>F</div> }
+pub fn registers() -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<I8080>>();
+    let psw = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.gp.af));
+    let set_psw = move |num: u16| -> Result<(), String> {
+        emu_signals.write.update(|emu| {
+            emu.cpu.registers.gp.af = num;
+        });
+        Ok(())
+    };
+
+    let bc = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.gp.bc));
+    let set_bc = move |num: u16| -> Result<(), String> {
+        emu_signals.write.update(|emu| {
+            emu.cpu.registers.gp.bc = num;
+        });
+        Ok(())
+    };
+
+    let de = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.gp.de));
+    let set_de = move |num: u16| -> Result<(), String> {
+        emu_signals.write.update(|emu| {
+            emu.cpu.registers.gp.de = num;
+        });
+        Ok(())
+    };
+
+    let hl = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.gp.hl));
+    let set_hl = move |num: u16| -> Result<(), String> {
+        emu_signals.write.update(|emu| {
+            emu.cpu.registers.gp.hl = num;
+        });
+        Ok(())
+    };
+
+    let sp = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.sp));
+    let set_sp = move |num: u16| -> Result<(), String> {
+        emu_signals.write.update(|emu| {
+            emu.cpu.registers.sp = num;
+        });
+        Ok(())
+    };
+
+    let pc = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.pc));
+    let set_pc = move |num: u16| -> Result<(), String> {
+        emu_signals.write.update(|emu| {
+            emu.cpu.registers.pc = num;
+        });
+        Ok(())
+    };
+
+    let (a, set_a) = half(
+        emu_signals,
+        true,
+        |emu| emu.cpu.registers.gp.af,
+        |emu| &mut emu.cpu.registers.gp.af,
+    );
+
+    let (f, set_f) = half(
+        emu_signals,
+        false,
+        |emu| emu.cpu.registers.gp.af,
+        |emu| &mut emu.cpu.registers.gp.af,
+    );
+
+    let (b, set_b) = half(
+        emu_signals,
+        true,
+        |emu| emu.cpu.registers.gp.bc,
+        |emu| &mut emu.cpu.registers.gp.bc,
+    );
+
+    let (c, set_c) = half(
+        emu_signals,
+        false,
+        |emu| emu.cpu.registers.gp.bc,
+        |emu| &mut emu.cpu.registers.gp.bc,
+    );
+
+    let (d, set_d) = half(
+        emu_signals,
+        true,
+        |emu| emu.cpu.registers.gp.de,
+        |emu| &mut emu.cpu.registers.gp.de,
+    );
+
+    let (e, set_e) = half(
+        emu_signals,
+        false,
+        |emu| emu.cpu.registers.gp.de,
+        |emu| &mut emu.cpu.registers.gp.de,
+    );
+
+    let (h, set_h) = half(
+        emu_signals,
+        true,
+        |emu| emu.cpu.registers.gp.hl,
+        |emu| &mut emu.cpu.registers.gp.hl,
+    );
+
+    let (l, set_l) = half(
+        emu_signals,
+        false,
+        |emu| emu.cpu.registers.gp.hl,
+        |emu| &mut emu.cpu.registers.gp.hl,
+    );
+
+    view! {
+        <div style:display="flex">
+            {Register("PSW", 16, psw, set_psw)} {Register("BC", 16, bc, set_bc)}
+            {Register("DE", 16, de, set_de)} {Register("HL", 16, hl, set_hl)}
+            {Register("SP", 16, sp, set_sp)} {Register("PC", 16, pc, set_pc)}
+        </div>
+        <div style:display="flex">
+            {Register("A", 8, a, set_a)} {Register("F", 8, f, set_f)}
+            {Register("B", 8, b, set_b)} {Register("C", 8, c, set_c)}
+            {Register("D", 8, d, set_d)} {Register("E", 8, e, set_e)}
+            {Register("H", 8, h, set_h)} {Register("L", 8, l, set_l)}
+        </div>
+        <Flags<I8080> />
+    }
 }
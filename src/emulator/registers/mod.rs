@@ -1,7 +1,7 @@
-use super::style;
-use emu_lib::cpu::i8080::I8080;
-use emu_lib::cpu::z80::Z80;
-use emu_lib::cpu::Cpu;
+use super::changes::ChangeSignals;
+use super::cpu::UiCpu;
+use super::memory::AddressReadSignals;
+use super::{style, EmuSignals};
 use emu_lib::emulator::Emulator;
 use leptos::logging::warn;
 use leptos::prelude::*;
@@ -12,41 +12,127 @@ use web_sys::HtmlInputElement;
 pub mod i8080;
 pub mod z80;
 
+/// Number base a register is shown and edited in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Base {
+    #[default]
+    Hex,
+    Unsigned,
+    Signed,
+    Binary,
+}
+
+impl Base {
+    /// Base shown after this one for a `bits` wide register, single bits
+    /// (IFF1, IFF2) have no sign.
+    pub fn next(&self, bits: u32) -> Self {
+        match self {
+            Base::Hex => Base::Unsigned,
+            Base::Unsigned if bits == 1 => Base::Binary,
+            Base::Unsigned => Base::Signed,
+            Base::Signed => Base::Binary,
+            Base::Binary => Base::Hex,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Base::Hex => "hex",
+            Base::Unsigned => "unsigned",
+            Base::Signed => "signed",
+            Base::Binary => "binary",
+        }
+    }
+
+    /// Formats the low `bits` bits of `value`.
+    pub fn format(&self, value: u16, bits: u32) -> String {
+        let shift = 16 - bits;
+        match self {
+            Base::Hex => format!("{:0width$X}", value, width = bits.div_ceil(4) as usize),
+            Base::Unsigned => value.to_string(),
+            Base::Signed => (((value << shift) as i16) >> shift).to_string(),
+            Base::Binary => format!("{:0width$b}", value, width = bits as usize),
+        }
+    }
+
+    /// Parses a value that fits in `bits` bits, negative values are stored
+    /// as two's complement.
+    pub fn parse(&self, text: &str, bits: u32) -> Option<u16> {
+        let text = text.trim();
+        let mask = (u32::MAX >> (32 - bits)) as u16;
+        let value = match self {
+            Base::Hex => u16::from_str_radix(text, 16).ok()?,
+            Base::Unsigned => text.parse::<u16>().ok()?,
+            Base::Signed => {
+                let value = text.parse::<i32>().ok()?;
+                let limit = 1i32 << (bits - 1);
+                if value < -limit || value >= limit {
+                    return None;
+                }
+                value as u16 & mask
+            }
+            Base::Binary => u16::from_str_radix(text, 2).ok()?,
+        };
+        (value <= mask).then_some(value)
+    }
+
+    fn max_length(&self, bits: u32) -> usize {
+        let mask = (u32::MAX >> (32 - bits)) as u16;
+        match self {
+            Base::Hex => bits.div_ceil(4) as usize,
+            Base::Unsigned => mask.to_string().len(),
+            Base::Signed => self.format(1 << (bits - 1), bits).len(),
+            Base::Binary => bits as usize,
+        }
+    }
+}
+
 // #[component]
+/// A `bits` wide register, clicking the name cycles the base it is shown in.
 fn Register(
     name: &'static str,
-    maxlength: usize,
-    get: Signal<String>,
-    set: impl Fn(&str) -> Result<(), std::num::ParseIntError> + 'static,
+    bits: u32,
+    get: Signal<u16>,
+    set: impl Fn(u16) -> Result<(), String> + 'static,
 ) -> impl IntoView {
+    let base = RwSignal::new(Base::default());
+    let text = move || base.get().format(get.get(), bits);
     let change = move |event|
         {
             let val = event_target_value(&event);
-            match set(&val) {
-                Ok(_) => {}
-                Err(_) => {
-                    warn!("Invalid hex value: {}",val);
-                    event_target::<HtmlInputElement>(&event)
-                        .borrow_mut()
-                        .set_value(&get());
-                }
+            let result = match base.get_untracked().parse(&val, bits) {
+                Some(value) => set(value),
+                None => Err(format!("Invalid {} value", base.get_untracked().name())),
+            };
+            if let Err(err) = result {
+                warn!("{}: {}", err, val);
+                event_target::<HtmlInputElement>(&event)
+                    .borrow_mut()
+                    .set_value(&text());
             }
         };
+    let changes = expect_context::<ChangeSignals>();
+    let class = move || format!("{} {}", style::tablecell, changes.register_class(name));
     view! {
         <table class=style::table>
             <thead>
                 <tr>
-                    <th class=style::tabletop>
+                    <th
+                        class=style::tabletop
+                        style:user-select="none"
+                        title=move || format!("{} (click to switch base)", base.get().name())
+                        on:click=move |_| base.update(|base| *base = base.next(bits))
+                    >
                         <span>{name}</span>
                     </th>
                 </tr>
             </thead>
             <tbody>
                 <tr>
-                    <th class=style::tablecell>
-                        <input style:width="100%" maxlength=maxlength
+                    <th class=class>
+                        <input style:width="100%" maxlength=move || base.get().max_length(bits)
                         on:change=change
-                        prop:value=get
+                        prop:value=text
                         />
                     </th>
                 </tr>
@@ -54,3 +140,157 @@ fn Register(
         </table>
     }
 }
+
+/// Value and setter of one byte of a register pair, kept in sync with the pair.
+fn half<C: UiCpu>(
+    emu_signals: EmuSignals<C>,
+    high: bool,
+    get: fn(&Emulator<C>) -> u16,
+    pair: fn(&mut Emulator<C>) -> &mut u16,
+) -> (Signal<u16>, impl Fn(u16) -> Result<(), String> + 'static) {
+    let shift = if high { 8 } else { 0 };
+    let value = Signal::derive(move || emu_signals.read.with(|emu| (get(emu) >> shift) & 0xFF));
+    let set = move |value: u16| {
+        emu_signals.write.update(|emu| {
+            let pair = pair(emu);
+            *pair = (*pair & !(0xFF << shift)) | (value << shift);
+        });
+        Ok(())
+    };
+    (value, set)
+}
+
+/// Bytes at the address held in a register pair, or the word on top of the
+/// stack for SP. Clicking shows the address in the memory editor.
+#[component]
+fn Pointer<C: UiCpu>(address: Signal<u16>, #[prop(optional)] stack: bool) -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let memory_address = expect_context::<AddressReadSignals>();
+    let text = move || {
+        let address = address.get();
+        emu_signals.read.with(|emu| {
+            let byte = |offset: u16| emu.memory.read_8(address.wrapping_add(offset)).ok();
+            match stack {
+                true => match (byte(0), byte(1)) {
+                    (Some(low), Some(high)) => format!("({:02X}{:02X})", high, low),
+                    _ => "(????)".to_string(),
+                },
+                false => (0..3)
+                    .map(|offset| match byte(offset) {
+                        Some(value) => format!("{:02X}", value),
+                        None => "??".to_string(),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            }
+        })
+    };
+    let title = match stack {
+        true => "Word at SP, likely a return address",
+        false => "Bytes at this address",
+    };
+    view! {
+        <div
+            class=style::tablebutton
+            style:font-size="0.7em"
+            title=title
+            on:click=move |_| memory_address.show(address.get_untracked())
+        >
+            <span>{text}</span>
+        </div>
+    }
+}
+
+#[component]
+fn FlagBit<C: UiCpu>(
+    bit: u8,
+    name: &'static str,
+    condition: Option<(&'static str, &'static str)>,
+    fixed: Option<bool>,
+) -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let mask = 1u8 << bit;
+    // Bits the cpu keeps at a fixed value cannot be toggled
+    if let Some(value) = fixed {
+        return view! {
+            <td
+                class=style::tablecell
+                title=format!("Bit {} (always {})", bit, value as u8)
+                style:padding="0.3rem"
+            >
+                <span>{name}</span>
+                <br />
+                <span>{value as u8}</span>
+            </td>
+        }
+        .into_any();
+    }
+    let set = move || emu_signals.read.with(|emu| emu.cpu.flags() & mask != 0);
+    let class = move || match set() {
+        true => style::tablebuttoninvert,
+        false => style::tablebutton,
+    };
+    let title = match name {
+        "X" | "Y" => format!("Bit {} (undocumented)", bit),
+        _ => format!("Bit {}", bit),
+    };
+    view! {
+        <td
+            class=class
+            title=title
+            style:padding="0.3rem"
+            on:click=move |_| {
+                emu_signals
+                    .write
+                    .update(|emu| {
+                        let flags = emu.cpu.flags();
+                        emu.cpu.set_flags(flags ^ mask);
+                    })
+            }
+        >
+            <span>{name}</span>
+            <br />
+            <span>
+                {move || match (condition, set()) {
+                    (Some((on, _)), true) => on.to_string(),
+                    (Some((_, off)), false) => off.to_string(),
+                    (None, set) => (set as u8).to_string(),
+                }}
+            </span>
+        </td>
+    }
+    .into_any()
+}
+
+#[component]
+pub fn Flags<C: UiCpu>() -> impl IntoView {
+    view! {
+        <table class=style::table>
+            <thead>
+                <tr>
+                    <th class=style::tabletop colspan=8>
+                        <span>"F"</span>
+                    </th>
+                </tr>
+            </thead>
+            <tbody>
+                <tr>
+                    {C::FLAGS
+                        .iter()
+                        .enumerate()
+                        .map(|(index, (name, condition, fixed))| {
+                            view! {
+                                <FlagBit<C>
+                                    bit=7 - index as u8
+                                    name=*name
+                                    condition=*condition
+                                    fixed=*fixed
+                                />
+                            }
+                        })
+                        .collect_view()}
+                </tr>
+            </tbody>
+        </table>
+    }
+}
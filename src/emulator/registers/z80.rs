@@ -1,148 +1,473 @@
-use crate::emulator::registers::Register;
+use crate::emulator::cpu::{FlagLayout, RegisterState, UiCpu};
+use crate::emulator::registers::{half, Flags, Pointer, Register};
 use crate::emulator::EmuSignals;
+use emu_lib::cpu::z80::Z80;
 use leptos::prelude::*;
+use leptos::tachys::view::any_view::AnyView;
 use leptos::{component, view, IntoView};
+use serde::Serialize;
+
+/// Copy of the Z80 register file, used to rewind or compare cpu state.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default, Serialize)]
+pub struct RegisterFile {
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+    pub af_alt: u16,
+    pub bc_alt: u16,
+    pub de_alt: u16,
+    pub hl_alt: u16,
+    pub sp: u16,
+    pub pc: u16,
+    pub ix: u16,
+    pub iy: u16,
+    pub i: u8,
+    pub r: u8,
+    pub iff1: bool,
+    pub iff2: bool,
+    pub im: u8,
+}
+
+impl RegisterFile {
+    pub fn capture(cpu: &Z80) -> Self {
+        let registers = &cpu.registers;
+        Self {
+            af: registers.gp.af,
+            bc: registers.gp.bc,
+            de: registers.gp.de,
+            hl: registers.gp.hl,
+            af_alt: registers.gp_alt.af,
+            bc_alt: registers.gp_alt.bc,
+            de_alt: registers.gp_alt.de,
+            hl_alt: registers.gp_alt.hl,
+            sp: registers.sp,
+            pc: registers.pc,
+            ix: registers.ix,
+            iy: registers.iy,
+            i: registers.i,
+            r: registers.r,
+            iff1: registers.iff1,
+            iff2: registers.iff2,
+            im: registers.im,
+        }
+    }
+
+    pub fn restore(&self, cpu: &mut Z80) {
+        let registers = &mut cpu.registers;
+        registers.gp.af = self.af;
+        registers.gp.bc = self.bc;
+        registers.gp.de = self.de;
+        registers.gp.hl = self.hl;
+        registers.gp_alt.af = self.af_alt;
+        registers.gp_alt.bc = self.bc_alt;
+        registers.gp_alt.de = self.de_alt;
+        registers.gp_alt.hl = self.hl_alt;
+        registers.sp = self.sp;
+        registers.pc = self.pc;
+        registers.ix = self.ix;
+        registers.iy = self.iy;
+        registers.i = self.i;
+        registers.r = self.r;
+        registers.iff1 = self.iff1;
+        registers.iff2 = self.iff2;
+        registers.im = self.im;
+    }
+}
+
+impl RegisterState for RegisterFile {
+    const CPU: &'static str = "Z80";
+    const WORD_NAMES: &'static [&'static str] = &[
+        "AF", "BC", "DE", "HL", "AF'", "BC'", "DE'", "HL'", "SP", "PC", "IX", "IY", "I", "R",
+        "IFF1", "IFF2", "IM",
+    ];
+
+    fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    fn get(&self, name: &str) -> Option<u16> {
+        Some(match name {
+            "AF" => self.af,
+            "BC" => self.bc,
+            "DE" => self.de,
+            "HL" => self.hl,
+            "AF'" => self.af_alt,
+            "BC'" => self.bc_alt,
+            "DE'" => self.de_alt,
+            "HL'" => self.hl_alt,
+            "SP" => self.sp,
+            "PC" => self.pc,
+            "IX" => self.ix,
+            "IY" => self.iy,
+            "I" => self.i as u16,
+            "R" => self.r as u16,
+            _ => return None,
+        })
+    }
+
+    fn columns(&self) -> Vec<(&'static str, u16, usize)> {
+        vec![
+            ("AF", self.af, 4),
+            ("BC", self.bc, 4),
+            ("DE", self.de, 4),
+            ("HL", self.hl, 4),
+            ("IX", self.ix, 4),
+            ("IY", self.iy, 4),
+            ("SP", self.sp, 4),
+            ("I", self.i as u16, 2),
+            ("R", self.r as u16, 2),
+        ]
+    }
+
+    fn to_words(&self) -> Vec<u16> {
+        vec![
+            self.af,
+            self.bc,
+            self.de,
+            self.hl,
+            self.af_alt,
+            self.bc_alt,
+            self.de_alt,
+            self.hl_alt,
+            self.sp,
+            self.pc,
+            self.ix,
+            self.iy,
+            self.i as u16,
+            self.r as u16,
+            self.iff1 as u16,
+            self.iff2 as u16,
+            self.im as u16,
+        ]
+    }
+
+    fn from_words(words: &[u16]) -> Option<Self> {
+        let [af, bc, de, hl, af_alt, bc_alt, de_alt, hl_alt, sp, pc, ix, iy, i, r, iff1, iff2, im] =
+            *words
+        else {
+            return None;
+        };
+        Some(Self {
+            af,
+            bc,
+            de,
+            hl,
+            af_alt,
+            bc_alt,
+            de_alt,
+            hl_alt,
+            sp,
+            pc,
+            ix,
+            iy,
+            i: i as u8,
+            r: r as u8,
+            iff1: iff1 != 0,
+            iff2: iff2 != 0,
+            im: im as u8,
+        })
+    }
+}
+
+impl UiCpu for Z80 {
+    type Registers = RegisterFile;
+    const FLAGS: FlagLayout = [
+        ("S", Some(("M", "P")), None),
+        ("Z", Some(("Z", "NZ")), None),
+        ("Y", None, None),
+        ("H", None, None),
+        ("X", None, None),
+        ("P/V", Some(("PE", "PO")), None),
+        ("N", None, None),
+        ("C", Some(("C", "NC")), None),
+    ];
+    const STEP_OVER_MNEMONICS: &'static [&'static str] = &[
+        "CALL", "RST", "DJNZ", "LDIR", "LDDR", "CPIR", "CPDR", "INIR", "INDR", "OTIR", "OTDR",
+    ];
+    const RETURN_MNEMONICS: &'static [&'static str] = &["RET", "RETI", "RETN"];
+
+    fn capture_registers(&self) -> RegisterFile {
+        RegisterFile::capture(self)
+    }
+
+    fn restore_registers(&mut self, registers: &RegisterFile) {
+        registers.restore(self);
+    }
+
+    fn set_pc(&mut self, pc: u16) {
+        self.registers.pc = pc;
+    }
+
+    fn flags(&self) -> u8 {
+        self.registers.gp.af as u8
+    }
+
+    fn set_flags(&mut self, flags: u8) {
+        self.registers.gp.af = (self.registers.gp.af & 0xFF00) | flags as u16;
+    }
+
+    fn registers_panel() -> AnyView {
+        view! { <registers /> }.into_any()
+    }
+}
 
 #[component]
 pub fn registers() -> impl IntoView {
-    let emu_signals = expect_context::<EmuSignals>();
-    let af = Signal::derive(move || {
-        emu_signals
-            .read
-            .with(|emu| format!("{:04X}", emu.cpu.registers.gp.af))
-    });
-    let set_af = move |value: &str| -> Result<(), std::num::ParseIntError> {
-        let num = u16::from_str_radix(value, 16)?;
+    let emu_signals = expect_context::<EmuSignals<Z80>>();
+    let af = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.gp.af));
+    let set_af = move |num: u16| -> Result<(), String> {
         emu_signals.write.update(|emu| {
             emu.cpu.registers.gp.af = num;
         });
         Ok(())
     };
 
-    let bc = Signal::derive(move || {
-        emu_signals
-            .read
-            .with(|emu| format!("{:04X}", emu.cpu.registers.gp.bc))
-    });
-    let set_bc = move |value: &str| -> Result<(), std::num::ParseIntError> {
-        let num = u16::from_str_radix(value, 16)?;
+    let bc = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.gp.bc));
+    let set_bc = move |num: u16| -> Result<(), String> {
         emu_signals.write.update(|emu| {
             emu.cpu.registers.gp.bc = num;
         });
         Ok(())
     };
 
-    let de = Signal::derive(move || {
-        emu_signals
-            .read
-            .with(|emu| format!("{:04X}", emu.cpu.registers.gp.de))
-    });
-    let set_de = move |value: &str| -> Result<(), std::num::ParseIntError> {
-        let num = u16::from_str_radix(value, 16)?;
+    let de = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.gp.de));
+    let set_de = move |num: u16| -> Result<(), String> {
         emu_signals.write.update(|emu| {
             emu.cpu.registers.gp.de = num;
         });
         Ok(())
     };
 
-    let hl = Signal::derive(move || {
-        emu_signals
-            .read
-            .with(|emu| format!("{:04X}", emu.cpu.registers.gp.hl))
-    });
-    let set_hl = move |value: &str| -> Result<(), std::num::ParseIntError> {
-        let num = u16::from_str_radix(value, 16)?;
+    let hl = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.gp.hl));
+    let set_hl = move |num: u16| -> Result<(), String> {
         emu_signals.write.update(|emu| {
             emu.cpu.registers.gp.hl = num;
         });
         Ok(())
     };
 
-    let sp = Signal::derive(move || {
-        emu_signals
-            .read
-            .with(|emu| format!("{:04X}", emu.cpu.registers.sp))
-    });
-    let set_sp = move |value: &str| -> Result<(), std::num::ParseIntError> {
-        let num = u16::from_str_radix(value, 16)?;
+    let sp = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.sp));
+    let set_sp = move |num: u16| -> Result<(), String> {
         emu_signals.write.update(|emu| {
             emu.cpu.registers.sp = num;
         });
         Ok(())
     };
 
-    let pc = Signal::derive(move || {
-        emu_signals
-            .read
-            .with(|emu| format!("{:04X}", emu.cpu.registers.pc))
-    });
-    let set_pc = move |value: &str| -> Result<(), std::num::ParseIntError> {
-        let num = u16::from_str_radix(value, 16)?;
+    let pc = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.pc));
+    let set_pc = move |num: u16| -> Result<(), String> {
         emu_signals.write.update(|emu| {
             emu.cpu.registers.pc = num;
         });
         Ok(())
     };
 
-    let ix = Signal::derive(move || {
-        emu_signals
-            .read
-            .with(|emu| format!("{:04X}", emu.cpu.registers.ix))
-    });
-    let set_ix = move |value: &str| -> Result<(), std::num::ParseIntError> {
-        let num = u16::from_str_radix(value, 16)?;
+    let ix = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.ix));
+    let set_ix = move |num: u16| -> Result<(), String> {
         emu_signals.write.update(|emu| {
             emu.cpu.registers.ix = num;
         });
         Ok(())
     };
 
-    let iy = Signal::derive(move || {
-        emu_signals
-            .read
-            .with(|emu| format!("{:04X}", emu.cpu.registers.iy))
-    });
-    let set_iy = move |value: &str| -> Result<(), std::num::ParseIntError> {
-        let num = u16::from_str_radix(value, 16)?;
+    let iy = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.iy));
+    let set_iy = move |num: u16| -> Result<(), String> {
         emu_signals.write.update(|emu| {
             emu.cpu.registers.iy = num;
         });
         Ok(())
     };
 
-    let i = Signal::derive(move || {
-        emu_signals
-            .read
-            .with(|emu| format!("{:02X}", emu.cpu.registers.i))
-    });
-    let set_i = move |value: &str| -> Result<(), std::num::ParseIntError> {
-        let num = u8::from_str_radix(value, 16)?;
+    let i = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.i as u16));
+    let set_i = move |num: u16| -> Result<(), String> {
+        emu_signals.write.update(|emu| {
+            emu.cpu.registers.i = num as u8;
+        });
+        Ok(())
+    };
+
+    let r = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.r as u16));
+    let set_r = move |num: u16| -> Result<(), String> {
+        emu_signals.write.update(|emu| {
+            emu.cpu.registers.r = num as u8;
+        });
+        Ok(())
+    };
+
+    let af_alt = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.gp_alt.af));
+    let set_af_alt = move |num: u16| -> Result<(), String> {
+        emu_signals.write.update(|emu| {
+            emu.cpu.registers.gp_alt.af = num;
+        });
+        Ok(())
+    };
+
+    let bc_alt = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.gp_alt.bc));
+    let set_bc_alt = move |num: u16| -> Result<(), String> {
+        emu_signals.write.update(|emu| {
+            emu.cpu.registers.gp_alt.bc = num;
+        });
+        Ok(())
+    };
+
+    let de_alt = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.gp_alt.de));
+    let set_de_alt = move |num: u16| -> Result<(), String> {
+        emu_signals.write.update(|emu| {
+            emu.cpu.registers.gp_alt.de = num;
+        });
+        Ok(())
+    };
+
+    let hl_alt = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.gp_alt.hl));
+    let set_hl_alt = move |num: u16| -> Result<(), String> {
+        emu_signals.write.update(|emu| {
+            emu.cpu.registers.gp_alt.hl = num;
+        });
+        Ok(())
+    };
+
+    let iff1 = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.iff1 as u16));
+    let set_iff1 = move |num: u16| -> Result<(), String> {
         emu_signals.write.update(|emu| {
-            emu.cpu.registers.i = num;
+            emu.cpu.registers.iff1 = num != 0;
         });
         Ok(())
     };
 
-    let r = Signal::derive(move || {
-        emu_signals
-            .read
-            .with(|emu| format!("{:02X}", emu.cpu.registers.r))
-    });
-    let set_r = move |value: &str| -> Result<(), std::num::ParseIntError> {
-        let num = u8::from_str_radix(value, 16)?;
+    let iff2 = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.iff2 as u16));
+    let set_iff2 = move |num: u16| -> Result<(), String> {
         emu_signals.write.update(|emu| {
-            emu.cpu.registers.r = num;
+            emu.cpu.registers.iff2 = num != 0;
         });
         Ok(())
     };
 
+    let im = Signal::derive(move || emu_signals.read.with(|emu| emu.cpu.registers.im as u16));
+    let set_im = move |num: u16| -> Result<(), String> {
+        if num > 2 {
+            return Err("Interrupt mode must be 0, 1 or 2".to_string());
+        }
+        emu_signals.write.update(|emu| {
+            emu.cpu.registers.im = num as u8;
+        });
+        Ok(())
+    };
+
+    let (a, set_a) = half(
+        emu_signals,
+        true,
+        |emu| emu.cpu.registers.gp.af,
+        |emu| &mut emu.cpu.registers.gp.af,
+    );
+
+    let (f, set_f) = half(
+        emu_signals,
+        false,
+        |emu| emu.cpu.registers.gp.af,
+        |emu| &mut emu.cpu.registers.gp.af,
+    );
+
+    let (b, set_b) = half(
+        emu_signals,
+        true,
+        |emu| emu.cpu.registers.gp.bc,
+        |emu| &mut emu.cpu.registers.gp.bc,
+    );
+
+    let (c, set_c) = half(
+        emu_signals,
+        false,
+        |emu| emu.cpu.registers.gp.bc,
+        |emu| &mut emu.cpu.registers.gp.bc,
+    );
+
+    let (d, set_d) = half(
+        emu_signals,
+        true,
+        |emu| emu.cpu.registers.gp.de,
+        |emu| &mut emu.cpu.registers.gp.de,
+    );
+
+    let (e, set_e) = half(
+        emu_signals,
+        false,
+        |emu| emu.cpu.registers.gp.de,
+        |emu| &mut emu.cpu.registers.gp.de,
+    );
+
+    let (h, set_h) = half(
+        emu_signals,
+        true,
+        |emu| emu.cpu.registers.gp.hl,
+        |emu| &mut emu.cpu.registers.gp.hl,
+    );
+
+    let (l, set_l) = half(
+        emu_signals,
+        false,
+        |emu| emu.cpu.registers.gp.hl,
+        |emu| &mut emu.cpu.registers.gp.hl,
+    );
+
+    let (ixh, set_ixh) = half(
+        emu_signals,
+        true,
+        |emu| emu.cpu.registers.ix,
+        |emu| &mut emu.cpu.registers.ix,
+    );
+
+    let (ixl, set_ixl) = half(
+        emu_signals,
+        false,
+        |emu| emu.cpu.registers.ix,
+        |emu| &mut emu.cpu.registers.ix,
+    );
+
+    let (iyh, set_iyh) = half(
+        emu_signals,
+        true,
+        |emu| emu.cpu.registers.iy,
+        |emu| &mut emu.cpu.registers.iy,
+    );
+
+    let (iyl, set_iyl) = half(
+        emu_signals,
+        false,
+        |emu| emu.cpu.registers.iy,
+        |emu| &mut emu.cpu.registers.iy,
+    );
+
     view! {
         <div style:display="flex">
-            {Register("AF", 4, af, set_af)} {Register("BC", 4, bc, set_bc)}
-            {Register("DE", 4, de, set_de)} {Register("HL", 4, hl, set_hl)}
-            {Register("SP", 4, sp, set_sp)} {Register("PC", 4, pc, set_pc)}
-            {Register("IX", 4, ix, set_ix)} {Register("IY", 4, iy, set_iy)}
-            {Register("I", 2, i, set_i)} {Register("R", 2, r, set_r)}
+            {Register("AF", 16, af, set_af)}
+            <div>{Register("BC", 16, bc, set_bc)} <Pointer<Z80> address=bc /></div>
+            <div>{Register("DE", 16, de, set_de)} <Pointer<Z80> address=de /></div>
+            <div>{Register("HL", 16, hl, set_hl)} <Pointer<Z80> address=hl /></div>
+            <div>{Register("SP", 16, sp, set_sp)} <Pointer<Z80> address=sp stack=true /></div>
+            {Register("PC", 16, pc, set_pc)}
+            <div>{Register("IX", 16, ix, set_ix)} <Pointer<Z80> address=ix /></div>
+            <div>{Register("IY", 16, iy, set_iy)} <Pointer<Z80> address=iy /></div>
+            {Register("I", 8, i, set_i)} {Register("R", 8, r, set_r)}
+        </div>
+        <div style:display="flex">
+            {Register("A", 8, a, set_a)} {Register("F", 8, f, set_f)}
+            {Register("B", 8, b, set_b)} {Register("C", 8, c, set_c)}
+            {Register("D", 8, d, set_d)} {Register("E", 8, e, set_e)}
+            {Register("H", 8, h, set_h)} {Register("L", 8, l, set_l)}
+            {Register("IXH", 8, ixh, set_ixh)} {Register("IXL", 8, ixl, set_ixl)}
+            {Register("IYH", 8, iyh, set_iyh)} {Register("IYL", 8, iyl, set_iyl)}
+        </div>
+        <div style:display="flex">
+            {Register("AF'", 16, af_alt, set_af_alt)} {Register("BC'", 16, bc_alt, set_bc_alt)}
+            {Register("DE'", 16, de_alt, set_de_alt)} {Register("HL'", 16, hl_alt, set_hl_alt)}
+            {Register("IFF1", 1, iff1, set_iff1)} {Register("IFF2", 1, iff2, set_iff2)}
+            {Register("IM", 2, im, set_im)}
         </div>
+        <Flags<Z80> />
     }
 }
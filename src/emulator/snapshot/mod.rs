@@ -0,0 +1,396 @@
+use super::breakpoints::expr::{Expr, Template};
+use super::breakpoints::Breakpoint;
+use super::control::runner::RunnerSignals;
+use super::cpu::{RegisterState, UiCpu};
+use super::{files, style, EmuSignals};
+use emu_lib::cpu::Cpu;
+use emu_lib::emulator::Emulator;
+use emu_lib::memory::MemoryDevice;
+use leptos::logging::warn;
+use leptos::prelude::*;
+use std::collections::BTreeMap;
+use web_sys::{Event, HtmlInputElement, Storage};
+
+const MAGIC: &[u8; 8] = b"EMUSNAP\0";
+const VERSION: u16 = 3;
+/// Oldest version that can still be read, it has no advanced breakpoints.
+const MIN_VERSION: u16 = 2;
+const STORAGE_PREFIX: &str = "emu_snapshot:";
+
+/// Complete machine state: cpu, breakpoints and the whole address space.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot<R> {
+    pub registers: R,
+    pub halted: bool,
+    pub breakpoints: Vec<u16>,
+    /// Conditional, counted and logging breakpoints, kept by `BreakpointSignals`.
+    pub advanced: BTreeMap<u16, Breakpoint>,
+    pub memory: Vec<u8>,
+}
+
+impl<R: RegisterState> Snapshot<R> {
+    pub fn capture<C: UiCpu<Registers = R>>(
+        emu: &Emulator<C>,
+        advanced: BTreeMap<u16, Breakpoint>,
+    ) -> Self {
+        Self {
+            registers: emu.cpu.capture_registers(),
+            halted: emu.cpu.halted(),
+            breakpoints: emu.breakpoints.clone(),
+            advanced,
+            memory: (0..emu.memory.size())
+                .map(|addr| emu.memory.read_8(addr as u16).unwrap_or_default())
+                .collect(),
+        }
+    }
+
+    /// Restores everything but the advanced breakpoints, which the caller
+    /// hands to `BreakpointSignals`.
+    pub fn restore<C: UiCpu<Registers = R>>(&self, emu: &mut Emulator<C>) -> Result<(), String> {
+        if self.memory.len() != emu.memory.size() {
+            return Err(format!(
+                "Snapshot holds {} bytes of memory but the machine has {}",
+                self.memory.len(),
+                emu.memory.size()
+            ));
+        }
+        // A failed restore puts back the memory already overwritten, so the
+        // machine is left as it was
+        let previous = Self::capture(emu, BTreeMap::new()).memory;
+        for (addr, value) in self.memory.iter().enumerate() {
+            if let Err(err) = emu.memory.write_8_force(addr as u16, *value) {
+                for (addr, value) in previous.iter().enumerate().take(addr) {
+                    let _ = emu.memory.write_8_force(addr as u16, *value);
+                }
+                return Err(format!("Failed to restore 0x{:04X}: {}", addr, err));
+            }
+        }
+        emu.cpu.restore_registers(&self.registers);
+        emu.cpu.set_halted(self.halted);
+        // An address is either a plain or an advanced breakpoint, never both
+        emu.breakpoints = self
+            .breakpoints
+            .iter()
+            .copied()
+            .filter(|address| !self.advanced.contains_key(address))
+            .collect();
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let words = self.registers.to_words();
+        let mut data = Vec::with_capacity(self.memory.len() + 64);
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.push(R::CPU.len() as u8);
+        data.extend_from_slice(R::CPU.as_bytes());
+        data.push(words.len() as u8);
+        for word in words {
+            data.extend_from_slice(&word.to_le_bytes());
+        }
+        data.push(self.halted as u8);
+        data.extend_from_slice(&(self.breakpoints.len() as u32).to_le_bytes());
+        for breakpoint in &self.breakpoints {
+            data.extend_from_slice(&breakpoint.to_le_bytes());
+        }
+        data.extend_from_slice(&(self.advanced.len() as u32).to_le_bytes());
+        for (address, breakpoint) in &self.advanced {
+            data.extend_from_slice(&address.to_le_bytes());
+            data.extend_from_slice(&breakpoint.ignore_count.to_le_bytes());
+            data.extend_from_slice(&breakpoint.hits.to_le_bytes());
+            push_text(
+                &mut data,
+                breakpoint.condition.as_ref().map(|(source, _)| source),
+            );
+            push_text(
+                &mut data,
+                breakpoint.log_message.as_ref().map(|(source, _)| source),
+            );
+        }
+        data.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        data.extend_from_slice(&self.memory);
+        data
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { data, pos: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err("Not a snapshot file".to_string());
+        }
+        let version = reader.u16()?;
+        if !(MIN_VERSION..=VERSION).contains(&version) {
+            return Err(format!("Unsupported snapshot version {}", version));
+        }
+        let cpu_length = reader.take(1)?[0] as usize;
+        let cpu = String::from_utf8_lossy(reader.take(cpu_length)?).to_string();
+        if cpu != R::CPU {
+            return Err(format!("Snapshot is for a {} cpu, not {}", cpu, R::CPU));
+        }
+        let word_count = reader.take(1)?[0] as usize;
+        let words = (0..word_count)
+            .map(|_| reader.u16())
+            .collect::<Result<Vec<_>, _>>()?;
+        let registers = R::from_words(&words).ok_or("Snapshot registers are corrupted")?;
+        let halted = reader.take(1)?[0];
+        let breakpoint_count = reader.u32()? as usize;
+        let breakpoints = (0..breakpoint_count)
+            .map(|_| reader.u16())
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut advanced = BTreeMap::new();
+        if version >= 3 {
+            for _ in 0..reader.u32()? {
+                let address = reader.u16()?;
+                let ignore_count = reader.u32()?;
+                let hits = reader.u32()?;
+                let condition = match reader.text()? {
+                    Some(source) => Some((source.clone(), Expr::parse(&source)?)),
+                    None => None,
+                };
+                let log_message = match reader.text()? {
+                    Some(source) => Some((source.clone(), Template::parse(&source)?)),
+                    None => None,
+                };
+                let breakpoint = Breakpoint {
+                    condition,
+                    ignore_count,
+                    hits,
+                    log_message,
+                };
+                advanced.insert(address, breakpoint);
+            }
+        }
+        let memory_size = reader.u32()? as usize;
+        let memory = reader.take(memory_size)?.to_vec();
+        Ok(Self {
+            registers,
+            halted: halted != 0,
+            breakpoints,
+            advanced,
+            memory,
+        })
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or("Snapshot file is truncated")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    /// Reads text written by `push_text`.
+    fn text(&mut self) -> Result<Option<String>, String> {
+        let length = self.u16()?;
+        if length == 0 {
+            return Ok(None);
+        }
+        let bytes = self.take(length as usize)?;
+        Ok(Some(String::from_utf8_lossy(bytes).to_string()))
+    }
+}
+
+/// Appends optional text with its length, empty text stands for `None`.
+fn push_text(data: &mut Vec<u8>, text: Option<&String>) {
+    let bytes = text.map(|text| text.as_bytes()).unwrap_or_default();
+    data.extend_from_slice(&(bytes.len() as u16).to_le_bytes());
+    data.extend_from_slice(bytes);
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn from_hex(text: &str) -> Result<Vec<u8>, String> {
+    (0..text.len())
+        .step_by(2)
+        .map(|i| {
+            text.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| "Corrupted quick-slot".to_string())
+        })
+        .collect()
+}
+
+fn local_storage() -> Option<Storage> {
+    window().local_storage().ok().flatten()
+}
+
+fn slot_names() -> Vec<String> {
+    let Some(storage) = local_storage() else {
+        return Vec::new();
+    };
+    let mut names = (0..storage.length().unwrap_or(0))
+        .filter_map(|i| storage.key(i).ok().flatten())
+        .filter_map(|key| key.strip_prefix(STORAGE_PREFIX).map(str::to_string))
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[component]
+pub fn SnapshotPanel<C: UiCpu>() -> impl IntoView {
+    let emu_signals = expect_context::<EmuSignals<C>>();
+    let runner = expect_context::<RunnerSignals<C>>();
+    let status = RwSignal::new(String::new());
+    let slots = RwSignal::new(slot_names());
+    let slot_name = RwSignal::new(String::new());
+
+    let restore = move |snapshot: Result<Snapshot<C::Registers>, String>| {
+        runner.stop();
+        let mut result = Ok(());
+        let mut advanced = None;
+        emu_signals.write.update(|emu| {
+            result = snapshot.and_then(|snapshot| {
+                snapshot.restore(emu)?;
+                advanced = Some(snapshot.advanced);
+                Ok(())
+            });
+        });
+        if let Some(advanced) = advanced {
+            runner.history.clear();
+            runner.breakpoints.list.set(advanced);
+        }
+        match result {
+            Ok(_) => status.set("Snapshot restored".to_string()),
+            Err(err) => {
+                warn!("Failed to restore snapshot: {}", err);
+                status.set(err);
+            }
+        }
+    };
+    let capture = move || {
+        let advanced = runner.breakpoints.list.get_untracked();
+        emu_signals
+            .read
+            .with_untracked(|emu| Snapshot::capture(emu, advanced))
+            .to_bytes()
+    };
+
+    let download = move |_| {
+        files::download_bytes("snapshot.emusnap", &capture());
+        status.set("Snapshot saved".to_string());
+    };
+    let upload = move |event: Event| {
+        let element = event_target::<HtmlInputElement>(&event);
+        files::read_input_file(&element, move |_, data| {
+            restore(Snapshot::from_bytes(&data));
+        });
+    };
+    let store = move |_| {
+        let name = slot_name.get_untracked().trim().to_string();
+        if name.is_empty() {
+            status.set("Enter a slot name".to_string());
+            return;
+        }
+        let result = local_storage().ok_or(()).and_then(|storage| {
+            storage
+                .set_item(&format!("{}{}", STORAGE_PREFIX, name), &to_hex(&capture()))
+                .map_err(|_| ())
+        });
+        match result {
+            Ok(_) => status.set(format!("Saved to slot \"{}\"", name)),
+            Err(_) => status.set("Browser storage is unavailable or full".to_string()),
+        }
+        slots.set(slot_names());
+    };
+    let load_slot = move |name: String| {
+        let data = local_storage()
+            .and_then(|storage| {
+                storage
+                    .get_item(&format!("{}{}", STORAGE_PREFIX, name))
+                    .ok()
+                    .flatten()
+            })
+            .ok_or_else(|| format!("Slot \"{}\" not found", name));
+        restore(
+            data.and_then(|text| from_hex(&text))
+                .and_then(|data| Snapshot::from_bytes(&data)),
+        );
+    };
+    let delete_slot = move |name: String| {
+        if let Some(storage) = local_storage() {
+            let _ = storage.remove_item(&format!("{}{}", STORAGE_PREFIX, name));
+        }
+        slots.set(slot_names());
+    };
+
+    view! {
+        <table style:width="100%" class=style::table>
+            <tr>
+                <th class=style::tablebutton style:padding="0.3rem" on:click=download>
+                    "Save snapshot"
+                </th>
+                <th class=style::tablebutton>
+                    <input
+                        on:change=upload
+                        type="file"
+                        accept=".emusnap"
+                        style:display="none"
+                        id="snapshot-input"
+                    />
+                    <label for="snapshot-input" style:padding="0.3rem">
+                        "Load snapshot"
+                    </label>
+                </th>
+                <th class=style::tablecell>
+                    <input
+                        style:width="100%"
+                        placeholder="Slot name"
+                        prop:value=slot_name
+                        on:change=move |event| slot_name.set(event_target_value(&event))
+                    />
+                </th>
+                <th class=style::tablebutton style:padding="0.3rem" on:click=store>
+                    "Quick save"
+                </th>
+            </tr>
+            <For each=move || slots.get() key=|name| name.clone() let:name>
+                <tr>
+                    <th class=style::tableleft colspan=2 style:padding="0.3rem">
+                        <span>{name.clone()}</span>
+                    </th>
+                    <th
+                        class=style::tablebutton
+                        style:padding="0.3rem"
+                        on:click={
+                            let name = name.clone();
+                            move |_| load_slot(name.clone())
+                        }
+                    >
+                        "Load"
+                    </th>
+                    <th
+                        class=style::tablebutton
+                        style:padding="0.3rem"
+                        on:click=move |_| delete_slot(name.clone())
+                    >
+                        "Delete"
+                    </th>
+                </tr>
+            </For>
+            <Show when=move || !status.get().is_empty()>
+                <tr>
+                    <th class=style::tableleft colspan=4 style:padding="0.3rem">
+                        <span>{move || status.get()}</span>
+                    </th>
+                </tr>
+            </Show>
+        </table>
+    }
+}
@@ -0,0 +1,157 @@
+use super::control::runner::{ClockSpeed, RunnerSignals};
+use super::cpu::UiCpu;
+use super::style;
+use leptos::prelude::*;
+
+/// Execution counters, fed by the runner after every run or step.
+#[derive(Clone, Copy)]
+pub struct StatsSignals {
+    pub cycles: RwSignal<u64>,
+    pub instructions: RwSignal<u64>,
+    /// Counters at the last stopwatch reset.
+    pub mark: RwSignal<(u64, u64)>,
+    /// T-states and instructions between the last two breakpoint stops.
+    pub last_lap: RwSignal<Option<(u64, u64)>>,
+    lap_start: StoredValue<Option<(u64, u64)>>,
+    /// Host time spent emulating during the last frame.
+    pub frame_ms: RwSignal<f64>,
+}
+
+impl Default for StatsSignals {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatsSignals {
+    pub fn new() -> Self {
+        Self {
+            cycles: RwSignal::new(0),
+            instructions: RwSignal::new(0),
+            mark: RwSignal::new((0, 0)),
+            last_lap: RwSignal::new(None),
+            lap_start: StoredValue::new(None),
+            frame_ms: RwSignal::new(0.0),
+        }
+    }
+
+    pub fn add(&self, cycles: u64, instructions: u64) {
+        if instructions == 0 {
+            return;
+        }
+        self.cycles.update(|total| *total += cycles);
+        self.instructions.update(|total| *total += instructions);
+    }
+
+    /// Called when execution stops on a breakpoint, closes the current lap.
+    pub fn breakpoint_hit(&self) {
+        let now = (
+            self.cycles.get_untracked(),
+            self.instructions.get_untracked(),
+        );
+        if let Some(start) = self.lap_start.get_value() {
+            self.last_lap.set(Some((now.0 - start.0, now.1 - start.1)));
+        }
+        self.lap_start.set_value(Some(now));
+    }
+
+    pub fn reset_stopwatch(&self) {
+        self.mark.set((
+            self.cycles.get_untracked(),
+            self.instructions.get_untracked(),
+        ));
+        self.lap_start.set_value(None);
+        self.last_lap.set(None);
+    }
+
+    pub fn reset(&self) {
+        self.cycles.set(0);
+        self.instructions.set(0);
+        self.reset_stopwatch();
+    }
+}
+
+fn format_time(cycles: u64, clock: ClockSpeed) -> String {
+    match clock {
+        ClockSpeed::Hz(hz) => {
+            let seconds = cycles as f64 / hz;
+            match seconds {
+                s if s >= 1.0 => format!("{:.3} s", s),
+                s if s >= 0.001 => format!("{:.3} ms", s * 1e3),
+                s => format!("{:.3} µs", s * 1e6),
+            }
+        }
+        ClockSpeed::Unlimited => "-".to_string(),
+    }
+}
+
+#[component]
+fn StatTr(name: &'static str, value: Signal<String>) -> impl IntoView {
+    view! {
+        <tr>
+            <th class=style::tabletop style:padding="0.3rem">
+                <span>{name}</span>
+            </th>
+            <td class=style::tableleft style:padding="0.3rem">
+                <span>{value}</span>
+            </td>
+        </tr>
+    }
+}
+
+#[component]
+pub fn StatsPanel<C: UiCpu>() -> impl IntoView {
+    let stats = expect_context::<StatsSignals>();
+    let runner = expect_context::<RunnerSignals<C>>();
+    let clock = runner.clock;
+
+    let cycles = Signal::derive(move || stats.cycles.get().to_string());
+    let instructions = Signal::derive(move || stats.instructions.get().to_string());
+    let elapsed = Signal::derive(move || format_time(stats.cycles.get(), clock.get()));
+    let stopwatch = Signal::derive(move || {
+        let (mark_cycles, mark_instructions) = stats.mark.get();
+        let cycles = stats.cycles.get() - mark_cycles;
+        format!(
+            "{} T / {} ins / {}",
+            cycles,
+            stats.instructions.get() - mark_instructions,
+            format_time(cycles, clock.get())
+        )
+    });
+    let lap = Signal::derive(move || match stats.last_lap.get() {
+        Some((cycles, instructions)) => format!(
+            "{} T / {} ins / {}",
+            cycles,
+            instructions,
+            format_time(cycles, clock.get())
+        ),
+        None => "-".to_string(),
+    });
+    let frame = Signal::derive(move || match runner.active.get() {
+        true => format!("{:.2} ms", stats.frame_ms.get()),
+        false => "-".to_string(),
+    });
+
+    view! {
+        <table style:width="100%" class=style::table>
+            <StatTr name="T-states" value=cycles />
+            <StatTr name="Instructions" value=instructions />
+            <StatTr name="Emulated time" value=elapsed />
+            <StatTr name="Stopwatch" value=stopwatch />
+            <StatTr name="Breakpoint to breakpoint" value=lap />
+            <StatTr name="Host frame time" value=frame />
+            <tr>
+                <th
+                    class=style::tablebutton
+                    style:padding="0.3rem"
+                    on:click=move |_| stats.reset_stopwatch()
+                >
+                    "Reset stopwatch"
+                </th>
+                <th class=style::tablebutton style:padding="0.3rem" on:click=move |_| stats.reset()>
+                    "Reset all"
+                </th>
+            </tr>
+        </table>
+    }
+}
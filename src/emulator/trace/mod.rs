@@ -0,0 +1,334 @@
+use super::cpu::{RegisterState, UiCpu};
+use super::files;
+use super::style;
+use leptos::html::Div;
+use leptos::logging::warn;
+use leptos::prelude::*;
+use serde::Serialize;
+use std::collections::VecDeque;
+use web_sys::HtmlInputElement;
+
+pub const DEFAULT_CAPACITY: usize = 10_000;
+const ROW_HEIGHT: f64 = 20.0;
+const VISIBLE_ROWS: usize = 12;
+
+/// One executed instruction, with the registers as they were before executing it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct TraceEntry<R> {
+    pub pc: u16,
+    pub bytes: Vec<u8>,
+    pub asm: String,
+    pub registers: R,
+}
+
+impl<R: RegisterState> TraceEntry<R> {
+    pub fn hex_bytes(&self) -> String {
+        self.bytes.iter().map(|b| format!("{:02X}", b)).collect()
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        format!("{:04X}", self.pc).contains(query)
+            || self.hex_bytes().contains(query)
+            || self.asm.to_ascii_uppercase().contains(query)
+    }
+
+    fn registers_text(&self) -> String {
+        self.registers
+            .columns()
+            .iter()
+            .map(|(name, value, digits)| format!("{}={:0width$X}", name, value, width = *digits))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Line in the usual "PC: instruction registers" layout of trace logs.
+    fn text_line(&self) -> String {
+        format!(
+            "{:04X}: {:<10} {:<20} {}",
+            self.pc,
+            self.hex_bytes(),
+            self.asm,
+            self.registers_text()
+        )
+    }
+
+    fn csv_line(&self) -> String {
+        let mut line = format!(
+            "{:04X},{},\"{}\"",
+            self.pc,
+            self.hex_bytes(),
+            self.asm.replace('"', "\"\"")
+        );
+        for (_, value, digits) in self.registers.columns() {
+            line.push_str(&format!(",{:0width$X}", value, width = digits));
+        }
+        line
+    }
+}
+
+/// Ring buffer of the last executed instructions.
+pub struct TraceBuffer<R> {
+    entries: VecDeque<TraceEntry<R>>,
+    capacity: usize,
+    /// Sequence number of the oldest entry, counting every entry ever recorded.
+    first: u64,
+}
+
+impl<R: RegisterState> TraceBuffer<R> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+            first: 0,
+        }
+    }
+
+    pub fn extend(&mut self, entries: Vec<TraceEntry<R>>) {
+        self.entries.extend(entries);
+        let excess = self.entries.len().saturating_sub(self.capacity);
+        self.entries.drain(..excess);
+        self.first += excess as u64;
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.extend(Vec::new());
+    }
+
+    pub fn clear(&mut self) {
+        self.first += self.entries.len() as u64;
+        self.entries.clear();
+    }
+
+    fn export_csv(&self) -> String {
+        let mut text = "pc,bytes,asm".to_string();
+        for (name, _, _) in R::default().columns() {
+            text.push(',');
+            text.push_str(&name.to_ascii_lowercase());
+        }
+        text.push('\n');
+        for entry in &self.entries {
+            text.push_str(&entry.csv_line());
+            text.push('\n');
+        }
+        text
+    }
+
+    fn export_text(&self) -> String {
+        let mut text = String::new();
+        for entry in &self.entries {
+            text.push_str(&entry.text_line());
+            text.push('\n');
+        }
+        text
+    }
+
+    fn export_json(&self) -> String {
+        serde_json::to_string_pretty(&self.entries).unwrap_or_default()
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TraceSignals<R: RegisterState> {
+    pub buffer: StoredValue<TraceBuffer<R>>,
+    pub enabled: RwSignal<bool>,
+    pub capacity: RwSignal<usize>,
+    /// Bumped every time the buffer changes, the buffer itself is not reactive.
+    pub version: RwSignal<usize>,
+}
+
+impl<R: RegisterState> Default for TraceSignals<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<R: RegisterState> TraceSignals<R> {
+    pub fn new() -> Self {
+        Self {
+            buffer: StoredValue::new(TraceBuffer::new(DEFAULT_CAPACITY)),
+            enabled: RwSignal::new(false),
+            capacity: RwSignal::new(DEFAULT_CAPACITY),
+            version: RwSignal::new(0),
+        }
+    }
+
+    pub fn record(&self, entries: Vec<TraceEntry<R>>) {
+        if entries.is_empty() {
+            return;
+        }
+        self.buffer.update_value(|buffer| buffer.extend(entries));
+        self.version.update(|version| *version += 1);
+    }
+
+    pub fn set_capacity(&self, capacity: usize) {
+        self.buffer
+            .update_value(|buffer| buffer.set_capacity(capacity));
+        self.capacity.set(capacity);
+        self.version.update(|version| *version += 1);
+    }
+
+    pub fn clear(&self) {
+        self.buffer.update_value(|buffer| buffer.clear());
+        self.version.update(|version| *version += 1);
+    }
+}
+
+#[component]
+fn TraceTr<R: RegisterState>(entry: TraceEntry<R>) -> impl IntoView {
+    view! {
+        <tr style:height=format!("{}px", ROW_HEIGHT)>
+            <td class=style::tableleft>
+                <span>{format!("{:04X}", entry.pc)}</span>
+            </td>
+            <td class=style::tableleft>
+                <span>{entry.hex_bytes()}</span>
+            </td>
+            <td class=style::tablecell style:text-align="left">
+                <span>{entry.asm.clone()}</span>
+            </td>
+            <td class=style::tableleft style:white-space="nowrap">
+                <span>{entry.registers_text()}</span>
+            </td>
+        </tr>
+    }
+}
+
+#[component]
+pub fn TracePanel<C: UiCpu>() -> impl IntoView {
+    let trace = expect_context::<TraceSignals<C::Registers>>();
+    let query = RwSignal::new(String::new());
+    let scroll_top = RwSignal::new(0.0);
+    let container = NodeRef::<Div>::new();
+
+    let matching = Memo::new(move |_| {
+        trace.version.track();
+        let query = query.get().to_ascii_uppercase();
+        trace.buffer.with_value(|buffer| {
+            buffer
+                .entries
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| query.is_empty() || entry.matches(&query))
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>()
+        })
+    });
+    let first_row = move || (scroll_top.get() / ROW_HEIGHT) as usize;
+    let visible = move || {
+        let first = first_row();
+        matching.with(|matching| {
+            let indices = matching.iter().skip(first).take(VISIBLE_ROWS + 1);
+            trace.buffer.with_value(|buffer| {
+                // The same instruction can repeat with identical state
+                // (HALT, JR $), so rows are keyed by sequence number
+                indices
+                    .filter_map(|index| {
+                        let entry = buffer.entries.get(*index)?.clone();
+                        Some((buffer.first + *index as u64, entry))
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+    };
+    let spacer_top = move || format!("{}px", first_row() as f64 * ROW_HEIGHT);
+    let spacer_bottom = move || {
+        let remaining = matching
+            .with(|matching| matching.len())
+            .saturating_sub(first_row() + VISIBLE_ROWS + 1);
+        format!("{}px", remaining as f64 * ROW_HEIGHT)
+    };
+    let export = move |kind: &str| {
+        let (name, text) = trace.buffer.with_value(|buffer| match kind {
+            "csv" => ("trace.csv", buffer.export_csv()),
+            "json" => ("trace.json", buffer.export_json()),
+            _ => ("trace.txt", buffer.export_text()),
+        });
+        files::download_text(name, &text);
+    };
+    let enabled_class = move || match trace.enabled.get() {
+        true => style::tablebuttoninvert,
+        false => style::tablebutton,
+    };
+
+    view! {
+        <table style:width="100%" class=style::table>
+            <tr>
+                <th
+                    class=enabled_class
+                    style:padding="0.3rem"
+                    on:click=move |_| trace.enabled.update(|enabled| *enabled = !*enabled)
+                >
+                    "Trace"
+                </th>
+                <th class=style::tablecell style:width="100%">
+                    <input
+                        style:width="100%"
+                        placeholder="Search"
+                        prop:value=query
+                        on:input=move |event| {
+                            query.set(event_target_value(&event));
+                            scroll_top.set(0.0);
+                            if let Some(container) = container.get() {
+                                container.set_scroll_top(0);
+                            }
+                        }
+                    />
+                </th>
+                <th class=style::tablecell>
+                    <input
+                        style:width="7ch"
+                        title="Trace length (instructions)"
+                        prop:value=move || trace.capacity.get().to_string()
+                        on:change=move |event| {
+                            match event_target_value(&event).parse::<usize>() {
+                                Ok(capacity) => trace.set_capacity(capacity),
+                                Err(_) => {
+                                    warn!("Invalid trace length");
+                                    event_target::<HtmlInputElement>(&event)
+                                        .set_value(&trace.capacity.get_untracked().to_string());
+                                }
+                            }
+                        }
+                    />
+                </th>
+                <th class=style::tablebutton style:padding="0.3rem" on:click=move |_| trace.clear()>
+                    "Clear"
+                </th>
+                <th class=style::tablebutton style:padding="0.3rem" on:click=move |_| export("txt")>
+                    "TXT"
+                </th>
+                <th class=style::tablebutton style:padding="0.3rem" on:click=move |_| export("csv")>
+                    "CSV"
+                </th>
+                <th
+                    class=style::tablebutton
+                    style:padding="0.3rem"
+                    on:click=move |_| export("json")
+                >
+                    "JSON"
+                </th>
+            </tr>
+        </table>
+        <div
+            node_ref=container
+            style:height=format!("{}px", ROW_HEIGHT * VISIBLE_ROWS as f64)
+            style:overflow-y="auto"
+            on:scroll=move |_| {
+                if let Some(container) = container.get() {
+                    scroll_top.set(container.scroll_top() as f64);
+                }
+            }
+        >
+            <table style:width="100%" class=style::table>
+                <tr style:height=spacer_top></tr>
+                <For
+                    each=visible
+                    key=|(sequence, _)| *sequence
+                    children=|(_, entry)| view! { <TraceTr entry /> }
+                />
+                <tr style:height=spacer_bottom></tr>
+            </table>
+        </div>
+    }
+}
@@ -0,0 +1,469 @@
+use super::style;
+use emu_lib::memory::MemoryDevice;
+use leptos::logging::warn;
+use leptos::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use web_sys::HtmlInputElement;
+
+const MAX_REPORTS: usize = 100;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Change,
+}
+
+impl WatchKind {
+    pub const ALL: [WatchKind; 3] = [WatchKind::Read, WatchKind::Write, WatchKind::Change];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WatchKind::Read => "Read",
+            WatchKind::Write => "Write",
+            WatchKind::Change => "Change",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    fn matches(&self, address: u16, kind: WatchKind) -> bool {
+        self.kind == kind && (self.start..=self.end).contains(&address)
+    }
+}
+
+/// A memory access that triggered a watchpoint.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct WatchHit {
+    pub pc: u16,
+    pub address: u16,
+    pub kind: WatchKind,
+    pub old: u8,
+    pub new: u8,
+}
+
+impl WatchHit {
+    pub fn describe(&self) -> String {
+        match self.kind {
+            WatchKind::Read => format!(
+                "PC {:04X} read {:04X}: {:02X}",
+                self.pc, self.address, self.new
+            ),
+            _ => format!(
+                "PC {:04X} wrote {:04X}: {:02X} -> {:02X}",
+                self.pc, self.address, self.old, self.new
+            ),
+        }
+    }
+}
+
+#[derive(Default)]
+struct WatchState {
+    watches: Vec<Watchpoint>,
+    hits: Vec<WatchHit>,
+}
+
+/// State shared between the watched devices and the runner.
+/// Accesses are only checked while armed, so the UI reading memory to
+/// display it never triggers a watchpoint.
+#[derive(Default)]
+pub struct WatchShared {
+    armed: AtomicBool,
+    state: Mutex<WatchState>,
+}
+
+impl WatchShared {
+    fn access(&self, address: u16, kind: WatchKind, old: u8, new: u8) {
+        if !self.armed.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Ok(mut state) = self.state.lock() {
+            if state.watches.iter().any(|watch| watch.matches(address, kind)) {
+                state.hits.push(WatchHit {
+                    pc: 0,
+                    address,
+                    kind,
+                    old,
+                    new,
+                });
+            }
+        }
+    }
+
+    fn set_watches(&self, watches: Vec<Watchpoint>) {
+        if let Ok(mut state) = self.state.lock() {
+            state.watches = watches;
+        }
+    }
+
+    /// Arms the watchpoints, returns false when there is nothing to watch.
+    pub fn arm(&self) -> bool {
+        let active = self
+            .state
+            .lock()
+            .map(|state| !state.watches.is_empty())
+            .unwrap_or(false);
+        self.armed.store(active, Ordering::Relaxed);
+        active
+    }
+
+    /// Re-arms after a `disarm`, without checking the watch list again.
+    pub fn resume(&self) {
+        self.armed.store(true, Ordering::Relaxed);
+    }
+
+    pub fn disarm(&self) {
+        self.armed.store(false, Ordering::Relaxed);
+    }
+
+    /// Takes the accesses recorded since the last call, attributing them to the instruction at `pc`.
+    pub fn take_hits(&self, pc: u16) -> Vec<WatchHit> {
+        self.state
+            .lock()
+            .map(|mut state| {
+                state
+                    .hits
+                    .drain(..)
+                    .map(|hit| WatchHit { pc, ..hit })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+/// Wraps a memory device and reports accesses to watched addresses.
+pub struct WatchedDevice {
+    base: u16,
+    inner: Box<dyn MemoryDevice>,
+    shared: Arc<WatchShared>,
+}
+
+impl WatchedDevice {
+    pub fn new(base: u16, inner: Box<dyn MemoryDevice>, shared: Arc<WatchShared>) -> Self {
+        Self {
+            base,
+            inner,
+            shared,
+        }
+    }
+
+    fn write_with(
+        &mut self,
+        addr: u16,
+        data: u8,
+        write: impl FnOnce(&mut Box<dyn MemoryDevice>) -> Result<(), &'static str>,
+    ) -> Result<(), &'static str> {
+        let old = self.inner.read_8(addr).unwrap_or_default();
+        write(&mut self.inner)?;
+        let address = self.base.wrapping_add(addr);
+        self.shared.access(address, WatchKind::Write, old, data);
+        if old != data {
+            self.shared.access(address, WatchKind::Change, old, data);
+        }
+        Ok(())
+    }
+}
+
+impl MemoryDevice for WatchedDevice {
+    fn size(&self) -> usize {
+        self.inner.size()
+    }
+
+    fn read_8(&self, addr: u16) -> Result<u8, &'static str> {
+        let value = self.inner.read_8(addr)?;
+        self.shared
+            .access(self.base.wrapping_add(addr), WatchKind::Read, value, value);
+        Ok(value)
+    }
+
+    fn write_8(&mut self, addr: u16, data: u8) -> Result<(), &'static str> {
+        self.write_with(addr, data, |inner| inner.write_8(addr, data))
+    }
+
+    fn write_8_force(&mut self, addr: u16, data: u8) -> Result<(), &'static str> {
+        self.write_with(addr, data, |inner| inner.write_8_force(addr, data))
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct WatchSignals {
+    pub shared: StoredValue<Arc<WatchShared>>,
+    pub list: RwSignal<Vec<Watchpoint>>,
+    pub hits: RwSignal<Vec<WatchHit>>,
+}
+
+impl WatchSignals {
+    pub fn new(shared: Arc<WatchShared>) -> Self {
+        Self {
+            shared: StoredValue::new(shared),
+            list: RwSignal::new(Vec::new()),
+            hits: RwSignal::new(Vec::new()),
+        }
+    }
+
+    fn update_list(&self, change: impl FnOnce(&mut Vec<Watchpoint>)) {
+        self.list.update(change);
+        let watches = self.list.get_untracked();
+        self.shared.with_value(|shared| shared.set_watches(watches));
+    }
+
+    pub fn add(&self, watch: Watchpoint) {
+        self.update_list(|list| list.push(watch));
+    }
+
+    pub fn replace(&self, index: usize, watch: Watchpoint) {
+        self.update_list(|list| {
+            if let Some(entry) = list.get_mut(index) {
+                *entry = watch;
+            }
+        });
+    }
+
+    pub fn remove(&self, index: usize) {
+        self.update_list(|list| {
+            if index < list.len() {
+                list.remove(index);
+            }
+        });
+    }
+
+    pub fn report(&self, hits: Vec<WatchHit>) {
+        if hits.is_empty() {
+            return;
+        }
+        self.hits.update(|list| {
+            list.extend(hits);
+            let excess = list.len().saturating_sub(MAX_REPORTS);
+            list.drain(..excess);
+        });
+    }
+}
+
+fn parse_address(event: &web_sys::Event, current: u16) -> Option<u16> {
+    let element = event_target::<HtmlInputElement>(event);
+    match u16::from_str_radix(&element.value(), 16) {
+        Ok(val) => Some(val),
+        Err(_) => {
+            warn!("Invalid hex value");
+            element.set_value(&format!("{:04X}", current));
+            None
+        }
+    }
+}
+
+#[component]
+fn WatchTr(index: usize) -> impl IntoView {
+    let watch_signals = expect_context::<WatchSignals>();
+    let watch = move || {
+        watch_signals
+            .list
+            .with(|list| list.get(index).copied())
+            .unwrap_or(Watchpoint {
+                start: 0,
+                end: 0,
+                kind: WatchKind::Write,
+            })
+    };
+    view! {
+        <tr>
+            <td class=style::tablecell>
+                <input
+                    style:width="5ch"
+                    maxlength=4
+                    prop:value=move || format!("{:04X}", watch().start)
+                    on:change=move |event| {
+                        let current = watch();
+                        if let Some(start) = parse_address(&event, current.start) {
+                            watch_signals
+                                .replace(
+                                    index,
+                                    Watchpoint {
+                                        start,
+                                        end: current.end.max(start),
+                                        ..current
+                                    },
+                                );
+                        }
+                    }
+                />
+            </td>
+            <td class=style::tablecell>
+                <input
+                    style:width="5ch"
+                    maxlength=4
+                    prop:value=move || format!("{:04X}", watch().end)
+                    on:change=move |event| {
+                        let current = watch();
+                        if let Some(end) = parse_address(&event, current.end) {
+                            watch_signals
+                                .replace(
+                                    index,
+                                    Watchpoint {
+                                        start: current.start.min(end),
+                                        end,
+                                        ..current
+                                    },
+                                );
+                        }
+                    }
+                />
+            </td>
+            <td class=style::tablecell>
+                <select
+                    style:width="100%"
+                    prop:value=move || watch().kind.name()
+                    on:change=move |event| {
+                        let value = event_target_value(&event);
+                        if let Some(kind) = WatchKind::ALL.iter().find(|kind| kind.name() == value) {
+                            watch_signals
+                                .replace(
+                                    index,
+                                    Watchpoint {
+                                        kind: *kind,
+                                        ..watch()
+                                    },
+                                );
+                        }
+                    }
+                >
+                    {WatchKind::ALL
+                        .iter()
+                        .map(|kind| view! { <option value=kind.name()>{kind.name()}</option> })
+                        .collect_view()}
+                </select>
+            </td>
+            <td class=style::tablebutton on:click=move |_| watch_signals.remove(index)>
+                <span>"✕"</span>
+            </td>
+        </tr>
+    }
+}
+
+#[component]
+pub fn WatchPanel() -> impl IntoView {
+    let watch_signals = expect_context::<WatchSignals>();
+    view! {
+        <table style:width="100%" class=style::table>
+            <thead>
+                <tr>
+                    <th class=style::tabletop>
+                        <span>"Start"</span>
+                    </th>
+                    <th class=style::tabletop>
+                        <span>"End"</span>
+                    </th>
+                    <th class=style::tabletop style:width="100%">
+                        <span>"Watch"</span>
+                    </th>
+                    <th
+                        class=style::tablebutton
+                        title="Add watchpoint"
+                        on:click=move |_| {
+                            watch_signals
+                                .add(Watchpoint {
+                                    start: 0,
+                                    end: 0,
+                                    kind: WatchKind::Write,
+                                })
+                        }
+                    >
+                        <span>"+"</span>
+                    </th>
+                </tr>
+            </thead>
+            <tbody>
+                <For
+                    each=move || 0..watch_signals.list.with(|list| list.len())
+                    key=|index| *index
+                    let:index
+                >
+                    <WatchTr index />
+                </For>
+                <For
+                    each=move || watch_signals.hits.get().into_iter().enumerate().rev().take(10)
+                    key=|(index, hit)| (*index, *hit)
+                    let:entry
+                >
+                    <tr>
+                        <td class=style::tableleft colspan=4>
+                            <span>{entry.1.describe()}</span>
+                        </td>
+                    </tr>
+                </For>
+            </tbody>
+        </table>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::emulator::heatmap::HeatShared;
+    use crate::emulator::history::WriteLog;
+    use crate::emulator::instrument;
+    use crate::emulator::memory::layout::Block;
+
+    /// A device wrapped like the ones of the machine, watching 0x10.
+    fn watched(kind: WatchKind) -> (Arc<WatchShared>, Box<dyn MemoryDevice>) {
+        let shared = Arc::new(WatchShared::default());
+        shared.set_watches(vec![Watchpoint {
+            start: 0x10,
+            end: 0x10,
+            kind,
+        }]);
+        let ram = Block::new(Arc::new(Mutex::new(vec![0; 0x100])), 0x100, false);
+        let device = instrument(
+            0,
+            Box::new(ram),
+            &WriteLog::default(),
+            &shared,
+            &Arc::new(HeatShared::default()),
+        );
+        assert!(shared.arm());
+        (shared, device)
+    }
+
+    #[test]
+    fn write_does_not_trigger_read_watch() {
+        let (shared, mut device) = watched(WatchKind::Read);
+        device.write_8(0x10, 0x42).unwrap();
+        device.write_8_force(0x10, 0x43).unwrap();
+        assert_eq!(shared.take_hits(0), Vec::new());
+        assert_eq!(device.read_8(0x10), Ok(0x43));
+        assert_eq!(
+            shared.take_hits(0x1234),
+            vec![WatchHit {
+                pc: 0x1234,
+                address: 0x10,
+                kind: WatchKind::Read,
+                old: 0x43,
+                new: 0x43,
+            }]
+        );
+    }
+
+    #[test]
+    fn write_and_change_watches() {
+        let (shared, mut device) = watched(WatchKind::Write);
+        device.write_8(0x10, 0).unwrap();
+        device.write_8(0x11, 1).unwrap();
+        assert_eq!(shared.take_hits(0).len(), 1);
+        assert_eq!(device.read_8(0x10), Ok(0));
+        assert_eq!(shared.take_hits(0), Vec::new());
+
+        let (shared, mut device) = watched(WatchKind::Change);
+        device.write_8(0x10, 0).unwrap();
+        assert_eq!(shared.take_hits(0), Vec::new());
+        device.write_8(0x10, 5).unwrap();
+        let hits = shared.take_hits(0);
+        assert_eq!((hits.len(), hits[0].old, hits[0].new), (1, 0, 5));
+    }
+}